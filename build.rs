@@ -0,0 +1,58 @@
+//! Generates the bindings of every contract ABI in `contract-abi/`.
+//!
+//! `contract-abi/SimpleStorage.json` becomes the `simple_storage` module of the library, holding
+//! the `SimpleStorage` binding. As the bindings are generated on every change to an ABI, a
+//! validator calling a function or building an event that no longer matches the ABI fails to
+//! compile.
+
+use ethers::contract::Abigen;
+use inflector::cases::snakecase::to_snake_case;
+
+use std::{env, fmt::Write, fs, path::Path};
+
+const ABI_DIR: &str = "contract-abi";
+
+fn main() {
+    let manifest_dir = env::var("CARGO_MANIFEST_DIR").unwrap();
+    let out_dir = env::var("OUT_DIR").unwrap();
+    let abi_dir = Path::new(&manifest_dir).join(ABI_DIR);
+    println!("cargo:rerun-if-changed={}", abi_dir.display());
+
+    // 1. List the ABIs, in a stable order so that the modules are too
+    let mut abi_paths: Vec<_> = fs::read_dir(&abi_dir)
+        .unwrap_or_else(|err| panic!("could not read {}: {}", abi_dir.display(), err))
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().and_then(|ext| ext.to_str()) == Some("json"))
+        .collect();
+    abi_paths.sort();
+
+    // 2. Generate the bindings of every ABI, and a module for each of them
+    let mut modules = String::new();
+    for abi_path in abi_paths.iter() {
+        println!("cargo:rerun-if-changed={}", abi_path.display());
+
+        let name = abi_path.file_stem().unwrap().to_str().unwrap();
+        let module = to_snake_case(name);
+        let bindings_path = Path::new(&out_dir).join(format!("{}.rs", module));
+        Abigen::new(name, abi_path.to_str().unwrap())
+            .and_then(|abigen| abigen.generate())
+            .and_then(|bindings| bindings.write_to_file(&bindings_path))
+            .unwrap_or_else(|err| {
+                panic!(
+                    "could not generate bindings for {}: {}",
+                    abi_path.display(),
+                    err
+                )
+            });
+
+        writeln!(
+            modules,
+            "pub mod {} {{\n    include!(concat!(env!(\"OUT_DIR\"), \"/{}.rs\"));\n}}\n",
+            module, module
+        )
+        .unwrap();
+    }
+
+    fs::write(Path::new(&out_dir).join("contracts.rs"), modules)
+        .expect("could not write the contract modules");
+}
@@ -0,0 +1,12 @@
+pragma solidity >=0.4.24;
+
+// Reverts on demand, for the tests of how reverted transactions are reported.
+contract Guarded {
+
+    uint256 public count;
+
+    function increment(bool allowed) public {
+        require(allowed, "not allowed");
+        count += 1;
+    }
+}
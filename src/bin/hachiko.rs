@@ -1 +1,651 @@
-fn main() {}
+use hachiko_lib::{
+    gas::{GasSnapshot, DEFAULT_GAS_SNAPSHOT, DEFAULT_GAS_TOLERANCE},
+    registry::{self, ValidatorEntry, WatchFn},
+    report::{json_report, junit_xml, TestCase},
+    runner::{run_all, RunnerConfig},
+    scaffold::{module_name, scaffold},
+    watch::{Divergence, WatchConfig},
+    DEFAULT_CONFIG_DIR,
+};
+
+use std::{env, fs, process, time::Duration};
+
+const USAGE: &str = "\
+Usage:
+    hachiko list
+    hachiko validate <name> [--config <dir>...] [--concurrency <n>] [<report options>] [<gas options>]
+    hachiko validate --all [--config <dir>...] [--concurrency <n>] [<report options>] [<gas options>]
+    hachiko watch <name> [--config <dir>] [--interval <ms>]
+    hachiko new <Name> --abi <file>
+
+Validate options:
+    --config <dir>            Validate with the configuration of every given directory
+    --concurrency <n>         Maximum number of validators running at the same time (default 4)
+
+Report options:
+    --junit <file>            Write a JUnit XML report
+    --json <file>             Write a JSON report
+
+Gas options:
+    --gas-snapshot <file>     Gas snapshot to compare with (default .hachiko-gas.toml)
+    --gas-tolerance <percent> Allowed gas increase over the snapshot (default 0)
+    --update-gas-snapshot     Overwrite the snapshot with the gas used by this run
+
+The gas used by a transition depends on the state it starts from, e.g. setValue costs more the
+first time lastSender is set. Record and compare snapshots against the same pre-state, such as
+a freshly deployed contract.";
+
+/// Where the gas snapshot is stored and how a run is compared with it.
+struct GasOptions {
+    snapshot: String,
+    tolerance: f64,
+    update: bool,
+}
+
+enum Command {
+    List,
+    New {
+        name: String,
+        abi: String,
+    },
+    Validate {
+        entries: Vec<ValidatorEntry>,
+        config_dirs: Vec<String>,
+        runner_config: RunnerConfig,
+        junit: Option<String>,
+        json: Option<String>,
+        gas: GasOptions,
+    },
+    Watch {
+        entry: ValidatorEntry,
+        watch: WatchFn,
+        config_dir: String,
+        watch_config: WatchConfig,
+    },
+}
+
+/// Flags taken by each command, any other flag is rejected.
+const VALIDATE_FLAGS: &[&str] = &[
+    "--all",
+    "--config",
+    "--concurrency",
+    "--junit",
+    "--json",
+    "--gas-snapshot",
+    "--gas-tolerance",
+    "--update-gas-snapshot",
+];
+const WATCH_FLAGS: &[&str] = &["--config", "--interval"];
+const NEW_FLAGS: &[&str] = &["--abi"];
+
+#[derive(Default)]
+struct Options {
+    /// Every flag given, in order
+    flags: Vec<String>,
+    names: Vec<String>,
+    all: bool,
+    config_dirs: Vec<String>,
+    interval: Option<Duration>,
+    concurrency: Option<usize>,
+    abi: Option<String>,
+    junit: Option<String>,
+    json: Option<String>,
+    gas_snapshot: Option<String>,
+    gas_tolerance: Option<f64>,
+    update_gas_snapshot: bool,
+}
+
+fn parse_options(args: &[String]) -> Result<Options, String> {
+    let mut options = Options::default();
+
+    let mut rest = args.iter();
+    while let Some(arg) = rest.next() {
+        if arg.starts_with("--") {
+            options.flags.push(arg.clone());
+        }
+
+        match arg.as_str() {
+            "--all" => options.all = true,
+            "--config" => {
+                let dir = rest.next().ok_or("--config expects a directory")?;
+                options.config_dirs.push(dir.clone());
+            }
+            "--interval" => {
+                let millis = rest
+                    .next()
+                    .and_then(|millis| millis.parse().ok())
+                    .ok_or("--interval expects a number of milliseconds")?;
+                options.interval = Some(Duration::from_millis(millis));
+            }
+            "--concurrency" => {
+                let concurrency = rest
+                    .next()
+                    .and_then(|concurrency| concurrency.parse().ok())
+                    .filter(|concurrency| *concurrency > 0)
+                    .ok_or("--concurrency expects a positive number")?;
+                options.concurrency = Some(concurrency);
+            }
+            "--abi" => {
+                let file = rest.next().ok_or("--abi expects a file")?;
+                options.abi = Some(file.clone());
+            }
+            "--junit" => {
+                let file = rest.next().ok_or("--junit expects a file")?;
+                options.junit = Some(file.clone());
+            }
+            "--json" => {
+                let file = rest.next().ok_or("--json expects a file")?;
+                options.json = Some(file.clone());
+            }
+            "--gas-snapshot" => {
+                let file = rest.next().ok_or("--gas-snapshot expects a file")?;
+                options.gas_snapshot = Some(file.clone());
+            }
+            "--gas-tolerance" => {
+                let percent = rest
+                    .next()
+                    .and_then(|percent| percent.parse().ok())
+                    .filter(|percent: &f64| *percent >= 0.0)
+                    .ok_or("--gas-tolerance expects a non-negative percentage")?;
+                options.gas_tolerance = Some(percent);
+            }
+            "--update-gas-snapshot" => options.update_gas_snapshot = true,
+            flag if flag.starts_with("--") => {
+                return Err(format!("unknown flag {}", flag));
+            }
+            name => options.names.push(name.to_string()),
+        }
+    }
+
+    Ok(options)
+}
+
+/// Rejects the first flag that `command` does not take.
+fn check_flags(command: &str, options: &Options, flags: &[&str]) -> Result<(), String> {
+    match options
+        .flags
+        .iter()
+        .find(|flag| !flags.contains(&flag.as_str()))
+    {
+        Some(flag) => Err(format!("{} does not apply to {}", flag, command)),
+        None => Ok(()),
+    }
+}
+
+fn find_entry(name: &str) -> Result<ValidatorEntry, String> {
+    registry::find(name).ok_or_else(|| format!("unknown validator {}", name))
+}
+
+fn parse_args(args: &[String]) -> Result<Command, String> {
+    match args.first().map(String::as_str) {
+        Some("list") if args.len() == 1 => Ok(Command::List),
+        Some("validate") => {
+            let options = parse_options(&args[1..])?;
+            check_flags("validate", &options, VALIDATE_FLAGS)?;
+            let entries = match (options.all, options.names.as_slice()) {
+                (true, []) => registry::validators(),
+                (false, [name]) => vec![find_entry(name)?],
+                _ => return Err("validate expects either a validator name or --all".to_string()),
+            };
+
+            let mut config_dirs = options.config_dirs;
+            if config_dirs.is_empty() {
+                config_dirs.push(DEFAULT_CONFIG_DIR.to_string());
+            }
+
+            let mut runner_config = RunnerConfig::default();
+            if let Some(concurrency) = options.concurrency {
+                runner_config.concurrency = concurrency;
+            }
+
+            Ok(Command::Validate {
+                entries,
+                config_dirs,
+                runner_config,
+                junit: options.junit,
+                json: options.json,
+                gas: GasOptions {
+                    snapshot: options
+                        .gas_snapshot
+                        .unwrap_or_else(|| DEFAULT_GAS_SNAPSHOT.to_string()),
+                    tolerance: options.gas_tolerance.unwrap_or(DEFAULT_GAS_TOLERANCE),
+                    update: options.update_gas_snapshot,
+                },
+            })
+        }
+        Some("watch") => {
+            let options = parse_options(&args[1..])?;
+            check_flags("watch", &options, WATCH_FLAGS)?;
+            let entry = match options.names.as_slice() {
+                [name] => find_entry(name)?,
+                _ => return Err("watch expects a validator name".to_string()),
+            };
+            let watch = entry
+                .watch
+                .ok_or_else(|| format!("{} cannot be watched", entry.name))?;
+
+            let config_dir = match options.config_dirs.as_slice() {
+                [] => DEFAULT_CONFIG_DIR.to_string(),
+                [config_dir] => config_dir.clone(),
+                _ => return Err("watch expects a single --config".to_string()),
+            };
+
+            let mut watch_config = WatchConfig::default();
+            if let Some(interval) = options.interval {
+                watch_config.poll_interval = interval;
+            }
+
+            Ok(Command::Watch {
+                entry,
+                watch,
+                config_dir,
+                watch_config,
+            })
+        }
+        Some("new") => {
+            let options = parse_options(&args[1..])?;
+            check_flags("new", &options, NEW_FLAGS)?;
+            match (options.names.as_slice(), options.abi) {
+                ([name], Some(abi)) => Ok(Command::New {
+                    name: name.clone(),
+                    abi,
+                }),
+                _ => Err("new expects a contract name and --abi".to_string()),
+            }
+        }
+        _ => Err("unknown command".to_string()),
+    }
+}
+
+fn report_case(case: &TestCase, label_config: bool) {
+    let name = if label_config {
+        format!("{} ({})", case.validator, case.config_dir)
+    } else {
+        case.validator.to_string()
+    };
+
+    match &case.result {
+        Ok(report) if report.passed() => {
+            println!("{} ... ok ({:?})", name, report.duration());
+        }
+        Ok(report) => {
+            println!("{} ... FAILED", name);
+            print!("{}", report);
+        }
+        Err(err) => {
+            eprintln!("{} ... ERROR", name);
+            eprintln!("  {}", err);
+        }
+    }
+}
+
+async fn validate_all(
+    entries: &[ValidatorEntry],
+    config_dirs: &[String],
+    runner_config: &RunnerConfig,
+) -> Vec<TestCase> {
+    let label_config = config_dirs.len() > 1;
+    let cases = run_all(entries, config_dirs, runner_config, |case| {
+        report_case(case, label_config)
+    })
+    .await;
+
+    let passed = cases.iter().filter(|case| case.passed()).count();
+    println!("\n{} passed, {} failed", passed, cases.len() - passed);
+
+    cases
+}
+
+/// Writes a report of the test cases to `path`.
+fn write_report(path: &str, contents: &str) -> bool {
+    match fs::write(path, contents) {
+        Ok(()) => true,
+        Err(err) => {
+            eprintln!("could not write report {}: {}", path, err);
+            false
+        }
+    }
+}
+
+/// Compares the gas used by this run with the snapshot, or overwrites the snapshot with it when
+/// updating. The snapshot file is only ever written when updating.
+fn check_gas(cases: &[TestCase], options: &GasOptions) -> bool {
+    let mut gas_used = GasSnapshot::default();
+    for case in cases.iter() {
+        if let Ok(report) = &case.result {
+            if let Some(gas) = report.gas_used {
+                gas_used.record(case.validator, report.transition, gas);
+            }
+        }
+    }
+
+    let mut snapshot = match GasSnapshot::load(&options.snapshot) {
+        Ok(snapshot) => snapshot,
+        Err(err) => {
+            eprintln!("gas snapshot ... ERROR\n  {}", err);
+            return false;
+        }
+    };
+
+    if options.update {
+        if snapshot.merge(&gas_used, true) {
+            if let Err(err) = snapshot.save(&options.snapshot) {
+                eprintln!("gas snapshot ... ERROR\n  {}", err);
+                return false;
+            }
+            println!("gas snapshot ... written to {}", options.snapshot);
+        }
+
+        return true;
+    }
+
+    let regressions = snapshot.regressions(&gas_used, options.tolerance);
+    if !regressions.is_empty() {
+        println!("gas snapshot ... FAILED");
+        for regression in regressions.iter() {
+            println!("  - {}", regression);
+        }
+
+        return false;
+    }
+
+    true
+}
+
+fn report_divergence(divergence: &Divergence) {
+    print!("{}", divergence);
+}
+
+async fn watch(
+    entry: &ValidatorEntry,
+    watch_fn: WatchFn,
+    config_dir: &str,
+    watch_config: WatchConfig,
+) -> bool {
+    let config = match (entry.fetch_config)(config_dir) {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("{} ... ERROR\n  {}", entry.name, err);
+            return false;
+        }
+    };
+
+    println!("watching {}", entry.name);
+    match watch_fn(config, watch_config, report_divergence).await {
+        Ok(report) => {
+            for reorg in report.reorgs.iter() {
+                match reorg.fork_block {
+                    Some(fork_block) => {
+                        println!("reorg of {} blocks after block {}", reorg.depth, fork_block)
+                    }
+                    None => println!("reorg deeper than the {} cached blocks", reorg.depth),
+                }
+            }
+
+            report.passed()
+        }
+        Err(err) => {
+            eprintln!("{} ... ERROR\n  {}", entry.name, err);
+            false
+        }
+    }
+}
+
+#[tokio::main(basic_scheduler)]
+async fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+
+    let command = match parse_args(&args) {
+        Ok(command) => command,
+        Err(err) => {
+            eprintln!("error: {}\n\n{}", err, USAGE);
+            process::exit(2);
+        }
+    };
+
+    match command {
+        Command::List => {
+            for entry in registry::validators().iter() {
+                println!("{}", entry.name);
+            }
+        }
+        Command::New { name, abi } => match scaffold(&name, &abi) {
+            Ok(paths) => {
+                for path in paths.iter() {
+                    println!("created {}", path);
+                }
+
+                let module = module_name(&name);
+                println!("\nTo finish setting up {}Validator:", name);
+                println!("  - declare `pub mod {}_validator;` in src/lib.rs", module);
+                println!(
+                    "  - register it in src/registry.rs with register_validator!(\"{}_validator\", {}Validator, watch)",
+                    module, name
+                );
+                println!("  - fill in the TODOs and the configuration file");
+            }
+            Err(err) => {
+                eprintln!("error: {}", err);
+                process::exit(1);
+            }
+        },
+        Command::Validate {
+            entries,
+            config_dirs,
+            runner_config,
+            junit,
+            json,
+            gas,
+        } => {
+            let cases = validate_all(&entries, &config_dirs, &runner_config).await;
+
+            let mut passed = cases.iter().all(TestCase::passed);
+            if let Some(path) = junit {
+                passed &= write_report(&path, &junit_xml(&cases));
+            }
+            if let Some(path) = json {
+                let report = serde_json::to_string_pretty(&json_report(&cases))
+                    .expect("a JSON value can always be serialized");
+                passed &= write_report(&path, &report);
+            }
+            passed &= check_gas(&cases, &gas);
+
+            if !passed {
+                process::exit(1);
+            }
+        }
+        Command::Watch {
+            entry,
+            watch: watch_fn,
+            config_dir,
+            watch_config,
+        } => {
+            if !watch(&entry, watch_fn, &config_dir, watch_config).await {
+                process::exit(1);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn args(line: &str) -> Vec<String> {
+        line.split_whitespace().map(String::from).collect()
+    }
+
+    #[test]
+    fn test_parse_options() {
+        let options = parse_options(&args(
+            "simple_storage_validator --config a --config b --interval 250 --concurrency 2 \
+             --abi a.json --junit r.xml --json r.json --gas-snapshot g.toml --gas-tolerance 2.5 \
+             --update-gas-snapshot",
+        ))
+        .unwrap();
+        assert_eq!(options.names, vec!["simple_storage_validator"]);
+        assert!(!options.all);
+        assert_eq!(options.config_dirs, vec!["a", "b"]);
+        assert_eq!(options.interval, Some(Duration::from_millis(250)));
+        assert_eq!(options.concurrency, Some(2));
+        assert_eq!(options.abi.as_deref(), Some("a.json"));
+        assert_eq!(options.junit.as_deref(), Some("r.xml"));
+        assert_eq!(options.json.as_deref(), Some("r.json"));
+        assert_eq!(options.gas_snapshot.as_deref(), Some("g.toml"));
+        assert_eq!(options.gas_tolerance, Some(2.5));
+        assert!(options.update_gas_snapshot);
+        assert_eq!(
+            options.flags,
+            vec![
+                "--config",
+                "--config",
+                "--interval",
+                "--concurrency",
+                "--abi",
+                "--junit",
+                "--json",
+                "--gas-snapshot",
+                "--gas-tolerance",
+                "--update-gas-snapshot",
+            ]
+        );
+
+        for (line, err) in [
+            ("--config", "--config expects a directory"),
+            (
+                "--interval soon",
+                "--interval expects a number of milliseconds",
+            ),
+            ("--concurrency 0", "--concurrency expects a positive number"),
+            (
+                "--gas-tolerance",
+                "--gas-tolerance expects a non-negative percentage",
+            ),
+            (
+                "--gas-tolerance -5",
+                "--gas-tolerance expects a non-negative percentage",
+            ),
+            ("--verbose", "unknown flag --verbose"),
+        ]
+        .iter()
+        {
+            assert_eq!(parse_options(&args(line)).err().as_deref(), Some(*err));
+        }
+    }
+
+    #[test]
+    fn test_usage_defaults() {
+        assert!(USAGE.contains(&format!(
+            "over the snapshot (default {})",
+            DEFAULT_GAS_TOLERANCE
+        )));
+        assert!(USAGE.contains(&format!("(default {})", DEFAULT_GAS_SNAPSHOT)));
+    }
+
+    #[test]
+    fn test_parse_args() {
+        assert!(matches!(parse_args(&args("list")), Ok(Command::List)));
+
+        match parse_args(&args("validate simple_storage_validator --concurrency 2")) {
+            Ok(Command::Validate {
+                entries,
+                config_dirs,
+                runner_config,
+                junit,
+                json,
+                gas,
+            }) => {
+                let names: Vec<&str> = entries.iter().map(|entry| entry.name).collect();
+                assert_eq!(names, vec!["simple_storage_validator"]);
+                assert_eq!(config_dirs, vec![DEFAULT_CONFIG_DIR]);
+                assert_eq!(runner_config.concurrency, 2);
+                assert!(junit.is_none() && json.is_none());
+                assert_eq!(gas.snapshot, DEFAULT_GAS_SNAPSHOT);
+                assert_eq!(gas.tolerance, DEFAULT_GAS_TOLERANCE);
+                assert!(!gas.update);
+            }
+            _ => panic!("expected a validate command"),
+        }
+
+        match parse_args(&args("validate --all --config a --config b")) {
+            Ok(Command::Validate {
+                entries,
+                config_dirs,
+                ..
+            }) => {
+                assert_eq!(entries.len(), registry::validators().len());
+                assert_eq!(config_dirs, vec!["a", "b"]);
+            }
+            _ => panic!("expected a validate command"),
+        }
+
+        match parse_args(&args(
+            "watch simple_storage_validator --config a --interval 100",
+        )) {
+            Ok(Command::Watch {
+                entry,
+                config_dir,
+                watch_config,
+                ..
+            }) => {
+                assert_eq!(entry.name, "simple_storage_validator");
+                assert_eq!(config_dir, "a");
+                assert_eq!(watch_config.poll_interval, Duration::from_millis(100));
+            }
+            _ => panic!("expected a watch command"),
+        }
+
+        match parse_args(&args("new Token --abi token.json")) {
+            Ok(Command::New { name, abi }) => {
+                assert_eq!(name, "Token");
+                assert_eq!(abi, "token.json");
+            }
+            _ => panic!("expected a new command"),
+        }
+
+        for (line, err) in [
+            ("", "unknown command"),
+            ("list extra", "unknown command"),
+            (
+                "validate",
+                "validate expects either a validator name or --all",
+            ),
+            (
+                "validate simple_storage_validator --all",
+                "validate expects either a validator name or --all",
+            ),
+            ("validate unknown", "unknown validator unknown"),
+            ("watch", "watch expects a validator name"),
+            (
+                "watch simple_storage_validator --config a --config b",
+                "watch expects a single --config",
+            ),
+            ("new Token", "new expects a contract name and --abi"),
+            (
+                "validate --all --interval 100",
+                "--interval does not apply to validate",
+            ),
+            (
+                "validate --all --abi a.json",
+                "--abi does not apply to validate",
+            ),
+            ("watch --all", "--all does not apply to watch"),
+            (
+                "watch simple_storage_validator --junit r.xml",
+                "--junit does not apply to watch",
+            ),
+            (
+                "watch simple_storage_validator --gas-tolerance 5",
+                "--gas-tolerance does not apply to watch",
+            ),
+            (
+                "new Token --abi a.json --config a",
+                "--config does not apply to new",
+            ),
+        ]
+        .iter()
+        {
+            assert_eq!(parse_args(&args(line)).err().as_deref(), Some(*err));
+        }
+    }
+}
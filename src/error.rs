@@ -0,0 +1,208 @@
+use config::ConfigError;
+use ethers::{contract::ContractError, providers::ProviderError};
+
+use std::{error::Error, fmt};
+
+/// Errors raised while configuring and connecting a validator.
+#[derive(Debug)]
+pub enum HachikoError {
+    /// The configuration file could not be found or read
+    MissingConfig { path: String, source: ConfigError },
+    /// The configuration file could not be parsed into a `FetchConfig`
+    InvalidConfig { path: String, source: ConfigError },
+    /// The configured private key is not a valid secp256k1 key
+    InvalidPrivateKey {
+        source: Box<dyn Error + Send + Sync>,
+    },
+    /// The configured contract address is not a valid address
+    InvalidAddress {
+        address: String,
+        source: Box<dyn Error + Send + Sync>,
+    },
+    /// The configured node URL could not be parsed
+    InvalidUrl {
+        url: String,
+        source: url::ParseError,
+    },
+    /// A JSON-RPC request to the node failed
+    Rpc(ProviderError),
+    /// A contract call or transaction failed
+    Contract(ContractError),
+    /// The gas snapshot file could not be read or written
+    GasSnapshot {
+        path: String,
+        source: std::io::Error,
+    },
+    /// The gas snapshot file could not be parsed
+    InvalidGasSnapshot {
+        path: String,
+        source: toml::de::Error,
+    },
+    /// The ABI a validator is scaffolded from could not be read
+    ReadAbi {
+        path: String,
+        source: std::io::Error,
+    },
+    /// The ABI a validator is scaffolded from is not supported
+    InvalidAbi { path: String, reason: String },
+    /// A file the scaffold would write already exists
+    FileExists(String),
+    /// A scaffolded file could not be written
+    WriteFile {
+        path: String,
+        source: std::io::Error,
+    },
+    /// The local node of a chain fixture could not be started
+    SpawnNode(String),
+    /// The contracts of a chain fixture could not be compiled
+    Compile(Box<dyn Error + Send + Sync>),
+    /// A chain fixture was asked to deploy a contract it did not compile
+    UnknownContract(String),
+    /// The local node could not revert to the snapshot of a chain fixture
+    Snapshot(String),
+}
+
+impl HachikoError {
+    /// Classifies an error returned while merging the configuration file at `path`.
+    pub fn load_config(path: &str, source: ConfigError) -> Self {
+        let path = path.to_string();
+        match source {
+            ConfigError::FileParse { .. } => HachikoError::InvalidConfig { path, source },
+            source => HachikoError::MissingConfig { path, source },
+        }
+    }
+}
+
+impl fmt::Display for HachikoError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            HachikoError::MissingConfig { path, source } => {
+                write!(f, "could not load config {}: {}", path, source)
+            }
+            HachikoError::InvalidConfig { path, source } => {
+                write!(f, "invalid config {}: {}", path, source)
+            }
+            HachikoError::InvalidPrivateKey { source } => {
+                write!(f, "invalid private key: {}", source)
+            }
+            HachikoError::InvalidAddress { address, source } => {
+                write!(f, "invalid address {}: {}", address, source)
+            }
+            HachikoError::InvalidUrl { url, source } => {
+                write!(f, "invalid url {}: {}", url, source)
+            }
+            HachikoError::Rpc(err) => write!(f, "rpc error: {}", err),
+            HachikoError::Contract(err) => write!(f, "contract error: {}", err),
+            HachikoError::GasSnapshot { path, source } => {
+                write!(f, "could not access gas snapshot {}: {}", path, source)
+            }
+            HachikoError::InvalidGasSnapshot { path, source } => {
+                write!(f, "invalid gas snapshot {}: {}", path, source)
+            }
+            HachikoError::ReadAbi { path, source } => {
+                write!(f, "could not read abi {}: {}", path, source)
+            }
+            HachikoError::InvalidAbi { path, reason } => {
+                write!(f, "invalid abi {}: {}", path, reason)
+            }
+            HachikoError::FileExists(path) => write!(f, "{} already exists", path),
+            HachikoError::WriteFile { path, source } => {
+                write!(f, "could not write {}: {}", path, source)
+            }
+            HachikoError::SpawnNode(reason) => {
+                write!(f, "could not start a local node: {}", reason)
+            }
+            HachikoError::Compile(err) => write!(f, "could not compile contracts: {}", err),
+            HachikoError::UnknownContract(name) => write!(f, "unknown contract {}", name),
+            HachikoError::Snapshot(reason) => write!(f, "could not revert chain: {}", reason),
+        }
+    }
+}
+
+impl Error for HachikoError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            HachikoError::MissingConfig { source, .. } => Some(source),
+            HachikoError::InvalidConfig { source, .. } => Some(source),
+            HachikoError::InvalidPrivateKey { source } => Some(source.as_ref()),
+            HachikoError::InvalidAddress { source, .. } => Some(source.as_ref()),
+            HachikoError::InvalidUrl { source, .. } => Some(source),
+            HachikoError::Rpc(err) => Some(err),
+            HachikoError::Contract(err) => Some(err),
+            HachikoError::GasSnapshot { source, .. } => Some(source),
+            HachikoError::InvalidGasSnapshot { source, .. } => Some(source),
+            HachikoError::ReadAbi { source, .. } => Some(source),
+            HachikoError::InvalidAbi { .. } => None,
+            HachikoError::FileExists(_) => None,
+            HachikoError::WriteFile { source, .. } => Some(source),
+            HachikoError::SpawnNode(_) => None,
+            HachikoError::Compile(err) => Some(err.as_ref()),
+            HachikoError::UnknownContract(_) => None,
+            HachikoError::Snapshot(_) => None,
+        }
+    }
+}
+
+impl From<ProviderError> for HachikoError {
+    fn from(err: ProviderError) -> Self {
+        HachikoError::Rpc(err)
+    }
+}
+
+impl From<ContractError> for HachikoError {
+    fn from(err: ContractError) -> Self {
+        HachikoError::Contract(err)
+    }
+}
+
+/// Errors that prevent a validation from running to completion.
+///
+/// A validation that runs but observes a mismatch is not an error, it is
+/// reported through a failed `ValidationReport`.
+#[derive(Debug)]
+pub enum ValidationError {
+    /// The validator could not be configured or connected
+    Setup(HachikoError),
+    /// The scenario cannot be run with the given validators
+    InvalidScenario(String),
+    /// The fuzzing configuration cannot generate any sequence
+    InvalidFuzzConfig(String),
+    /// A contract call, transaction or log query failed
+    Contract(ContractError),
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ValidationError::Setup(err) => write!(f, "setup error: {}", err),
+            ValidationError::InvalidScenario(reason) => write!(f, "invalid scenario: {}", reason),
+            ValidationError::InvalidFuzzConfig(reason) => {
+                write!(f, "invalid fuzz configuration: {}", reason)
+            }
+            ValidationError::Contract(err) => write!(f, "contract error: {}", err),
+        }
+    }
+}
+
+impl Error for ValidationError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ValidationError::Setup(err) => Some(err),
+            ValidationError::InvalidScenario(_) => None,
+            ValidationError::InvalidFuzzConfig(_) => None,
+            ValidationError::Contract(err) => Some(err),
+        }
+    }
+}
+
+impl From<HachikoError> for ValidationError {
+    fn from(err: HachikoError) -> Self {
+        ValidationError::Setup(err)
+    }
+}
+
+impl From<ContractError> for ValidationError {
+    fn from(err: ContractError) -> Self {
+        ValidationError::Contract(err)
+    }
+}
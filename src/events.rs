@@ -0,0 +1,225 @@
+use ethers::{
+    contract::ContractError,
+    core::{
+        abi::{Abi, InvalidOutputType, RawLog},
+        types::{Log, U64},
+    },
+};
+
+use std::{fmt, future::Future};
+
+use crate::ContractEvents;
+
+/// Number of blocks queried at once by `query_range`, unless the node rejects the chunk.
+pub const DEFAULT_CHUNK_SIZE: u64 = 1000;
+
+/// Whether a node rejected a log query because the range holds too many results.
+///
+/// Nodes do not agree on an error code for this, so the error message is matched instead.
+pub fn is_too_many_results(message: &str) -> bool {
+    let message = message.to_lowercase();
+
+    [
+        "query returned more than",
+        "too many",
+        "limit exceeded",
+        "response size exceeded",
+    ]
+    .iter()
+    .any(|pattern| message.contains(pattern))
+}
+
+/// Runs `query` over the blocks `from` to `to` (inclusive) in chunks of at most `chunk_size`.
+///
+/// A chunk rejected by the node for holding too many results is halved and retried, and the
+/// chunk size grows back to `chunk_size` after every successful query. Results are returned in
+/// the order of the chunks.
+pub async fn query_range<T, E, F, Fut>(
+    from: U64,
+    to: U64,
+    chunk_size: u64,
+    mut query: F,
+) -> Result<Vec<T>, E>
+where
+    E: fmt::Display,
+    F: FnMut(U64, U64) -> Fut,
+    Fut: Future<Output = Result<Vec<T>, E>>,
+{
+    let chunk_size = chunk_size.max(1);
+    let to = to.as_u64();
+
+    let mut results = vec![];
+    let mut start = from.as_u64();
+    let mut size = chunk_size;
+
+    while start <= to {
+        let end = to.min(start.saturating_add(size - 1));
+
+        match query(U64::from(start), U64::from(end)).await {
+            Ok(chunk) => {
+                results.extend(chunk);
+                start = end + 1;
+                size = size.saturating_mul(2).min(chunk_size);
+            }
+            Err(err) if end > start && is_too_many_results(&err.to_string()) => {
+                size = (end - start + 1) / 2;
+            }
+            Err(err) => return Err(err),
+        }
+    }
+
+    Ok(results)
+}
+
+/// Sorts logs by block number, then by their index within the block.
+pub fn sort_logs(logs: &mut Vec<Log>) {
+    logs.sort_by_key(|log| (log.block_number, log.log_index));
+}
+
+/// Decodes a raw log into one of the events of `D`, looking its event up in `abi` by signature.
+pub fn decode_event<D: ContractEvents>(abi: &Abi, log: Log) -> Result<D, ContractError> {
+    let signature = log.topics.first().cloned().unwrap_or_default();
+    let event = abi
+        .events()
+        .find(|event| event.signature() == signature)
+        .ok_or_else(|| InvalidOutputType(format!("no event with signature {:?}", signature)))?;
+
+    let parsed = event.parse_log(RawLog {
+        topics: log.topics,
+        data: log.data.0,
+    })?;
+    let tokens = parsed.params.into_iter().map(|param| param.value).collect();
+
+    Ok(D::decode(signature, tokens)?)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use ethers::core::{
+        abi::{Detokenize, Token},
+        types::{Address, TransactionReceipt, H256, U256},
+    };
+    use validator_derive::add_base_state_transition;
+
+    /// Defines a filter with a single parameter, as abigen would for an event.
+    macro_rules! filter {
+        ($name:ident, $signature:expr, $variant:ident($param:ty)) => {
+            #[derive(Clone, Debug, Default, PartialEq)]
+            struct $name($param);
+
+            impl $name {
+                fn signature() -> H256 {
+                    H256::from_low_u64_be($signature)
+                }
+            }
+
+            impl Detokenize for $name {
+                fn from_tokens(tokens: Vec<Token>) -> Result<Self, InvalidOutputType> {
+                    match tokens.as_slice() {
+                        [Token::$variant(param)] => Ok($name(*param)),
+                        _ => Err(InvalidOutputType(format!("not a {}", stringify!($name)))),
+                    }
+                }
+            }
+        };
+    }
+
+    filter!(DepositFilter, 1, Uint(U256));
+    filter!(WithdrawalFilter, 2, Uint(U256));
+    filter!(OwnerChangedFilter, 3, Address(Address));
+
+    #[add_base_state_transition(DepositFilter, WithdrawalFilter, OwnerChangedFilter)]
+    #[derive(Clone, Debug, Default, Builder, PartialEq)]
+    struct VaultStateTransition {}
+
+    #[test]
+    fn test_decode_events_of_several_types() {
+        let amount = vec![Token::Uint(U256::from(10))];
+        let owner = vec![Token::Address(Address::from_low_u64_be(1))];
+
+        assert_eq!(
+            VaultEvent::signatures(),
+            vec![
+                DepositFilter::signature(),
+                WithdrawalFilter::signature(),
+                OwnerChangedFilter::signature()
+            ]
+        );
+
+        // Events sharing parameter types are told apart by their signature
+        let deposit = VaultEvent::decode(DepositFilter::signature(), amount.clone()).unwrap();
+        assert_eq!(deposit, VaultEvent::Deposit(DepositFilter(U256::from(10))));
+        assert_eq!(deposit.signature(), DepositFilter::signature());
+        let withdrawal = VaultEvent::decode(WithdrawalFilter::signature(), amount.clone()).unwrap();
+        assert_eq!(
+            withdrawal,
+            VaultEvent::Withdrawal(WithdrawalFilter(U256::from(10)))
+        );
+        assert!(VaultEvent::decode(H256::zero(), amount.clone()).is_err());
+
+        // Without it, the tokens must match exactly one event
+        assert!(VaultEvent::from_tokens(amount).is_err());
+        assert_eq!(
+            VaultEvent::from_tokens(owner).unwrap(),
+            VaultEvent::OwnerChanged(OwnerChangedFilter(Address::from_low_u64_be(1)))
+        );
+        assert!(VaultEvent::from_tokens(vec![]).is_err());
+    }
+
+    #[test]
+    fn test_is_too_many_results() {
+        assert!(is_too_many_results(
+            "query returned more than 10000 results"
+        ));
+        assert!(is_too_many_results("Log response size exceeded."));
+        assert!(!is_too_many_results("execution reverted"));
+    }
+
+    #[tokio::test]
+    async fn test_query_range_halves_rejected_chunks() {
+        let mut queries = vec![];
+
+        // A node that rejects any range spanning more than 3 blocks
+        let blocks = query_range(U64::from(1), U64::from(10), 8, |from, to| {
+            queries.push((from.as_u64(), to.as_u64()));
+            let result = if (to - from).as_u64() >= 3 {
+                Err("query returned more than 10000 results".to_string())
+            } else {
+                Ok((from.as_u64()..=to.as_u64()).collect::<Vec<u64>>())
+            };
+
+            async move { result }
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(blocks, (1..=10).collect::<Vec<u64>>());
+        assert_eq!(
+            queries,
+            vec![
+                (1, 8),
+                (1, 4),
+                (1, 2),
+                (3, 6),
+                (3, 4),
+                (5, 8),
+                (5, 6),
+                (7, 10),
+                (7, 8),
+                (9, 10)
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_query_range_fails_on_single_block() {
+        let result = query_range(U64::from(1), U64::from(4), 4, |_, _| async {
+            Err::<Vec<u64>, _>("too many results".to_string())
+        })
+        .await;
+
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,261 @@
+use ethers::{
+    contract::ContractFactory,
+    core::{
+        abi::Tokenize,
+        types::{Address, U256},
+    },
+    providers::{Http, JsonRpcClient, Provider, ProviderError},
+    signers::Wallet,
+    utils::{CompiledContract, Ganache, GanacheInstance, Solc},
+};
+
+use std::{
+    any::Any, collections::HashMap, convert::TryFrom, panic, str::FromStr, sync::Arc,
+    time::Duration,
+};
+
+#[cfg(test)]
+use crate::{simple_storage_validator::SimpleStorageValidator, ValidatorBase};
+use crate::{HachikoError, ValidatorConfig};
+
+/// Directory of the Solidity sources compiled by a chain fixture.
+pub const CONTRACT_SRC_DIR: &str = "contract-src";
+
+/// Compiles every contract in `src_dir`, by name.
+///
+/// solc must be installed.
+pub fn compile(src_dir: &str) -> Result<HashMap<String, CompiledContract>, HachikoError> {
+    Solc::new(&format!("{}/*.sol", src_dir))
+        .build()
+        .map_err(|err| HachikoError::Compile(err.into()))
+}
+
+/// The message a panic was raised with.
+fn panic_message(panic: Box<dyn Any + Send>) -> String {
+    match panic.downcast::<String>() {
+        Ok(message) => *message,
+        Err(panic) => panic.downcast_ref::<&str>().map_or_else(
+            || "unknown error".to_string(),
+            |message| message.to_string(),
+        ),
+    }
+}
+
+/// A local node with the contracts of `contract-src/` compiled, ready to be deployed.
+///
+/// The node is spawned once and reset between validations: `snapshot` records the chain as it
+/// is, e.g. right after deploying, and `reset` reverts to it with `evm_snapshot`/`evm_revert`.
+/// The node is stopped when the fixture is dropped.
+pub struct ChainFixture {
+    ganache: GanacheInstance,
+    rpc: Http,
+    contracts: HashMap<String, CompiledContract>,
+    /// Id of the snapshot `reset` reverts to
+    snapshot: Option<U256>,
+}
+
+impl ChainFixture {
+    /// Spawns a Ganache node and compiles every contract in `contract-src/`.
+    ///
+    /// Ganache and solc must be installed.
+    pub fn spawn() -> Result<Self, HachikoError> {
+        Self::spawn_with(CONTRACT_SRC_DIR)
+    }
+
+    /// Spawns a Ganache node and compiles every contract in `src_dir`.
+    ///
+    /// Fails rather than panics if the node cannot be started.
+    pub fn spawn_with(src_dir: &str) -> Result<Self, HachikoError> {
+        let contracts = compile(src_dir)?;
+
+        // Ganache panics when the node cannot be started, e.g. when ganache-cli is not installed
+        let ganache = panic::catch_unwind(|| Ganache::new().spawn())
+            .map_err(|panic| HachikoError::SpawnNode(panic_message(panic)))?;
+        let rpc = Http::from_str(&ganache.endpoint()).map_err(|err| HachikoError::InvalidUrl {
+            url: ganache.endpoint(),
+            source: err,
+        })?;
+
+        Ok(ChainFixture {
+            ganache,
+            rpc,
+            contracts,
+            snapshot: None,
+        })
+    }
+
+    pub fn endpoint(&self) -> String {
+        self.ganache.endpoint()
+    }
+
+    /// Wallets of the accounts the node funded at genesis.
+    pub fn wallets(&self) -> Vec<Wallet> {
+        self.ganache
+            .keys()
+            .iter()
+            .map(|key| key.clone().into())
+            .collect()
+    }
+
+    /// Wallet of the `index`th funded account.
+    pub fn wallet(&self, index: usize) -> Wallet {
+        self.ganache.keys()[index].clone().into()
+    }
+
+    /// Deploys the compiled contract `name` from `wallet`, and returns its address.
+    pub async fn deploy<T: Tokenize>(
+        &self,
+        name: &str,
+        wallet: &Wallet,
+        constructor_args: T,
+    ) -> Result<Address, HachikoError> {
+        let contract = self
+            .contracts
+            .get(name)
+            .ok_or_else(|| HachikoError::UnknownContract(name.to_string()))?;
+
+        let provider = Provider::<Http>::try_from(self.endpoint())
+            .map_err(|err| HachikoError::InvalidUrl {
+                url: self.endpoint(),
+                source: err,
+            })?
+            .interval(Duration::from_millis(10u64));
+        let client = Arc::new(wallet.clone().connect(provider));
+
+        let factory = ContractFactory::new(contract.abi.clone(), contract.bytecode.clone(), client);
+        let contract = factory.deploy(constructor_args)?.send().await?;
+
+        Ok(contract.address())
+    }
+
+    /// Configuration of a validator sending transactions to the contract at `address` from
+    /// `wallet`.
+    pub fn validator_config(&self, wallet: &Wallet, address: Address) -> ValidatorConfig {
+        ValidatorConfig::new(wallet, &address, &self.endpoint())
+    }
+
+    /// Records the chain as it is now, for `reset` to revert to.
+    pub async fn snapshot(&mut self) -> Result<(), HachikoError> {
+        let id: U256 = self
+            .rpc
+            .request("evm_snapshot", ())
+            .await
+            .map_err(ProviderError::from)?;
+        self.snapshot = Some(id);
+
+        Ok(())
+    }
+
+    /// Reverts the chain to the last snapshot, discarding every block mined since.
+    ///
+    /// The node forgets a snapshot once reverted to, so a new one is recorded right away and the
+    /// fixture can be reset again before the next validation.
+    pub async fn reset(&mut self) -> Result<(), HachikoError> {
+        let id = self
+            .snapshot
+            .take()
+            .ok_or_else(|| HachikoError::Snapshot("no snapshot was recorded".to_string()))?;
+
+        let reverted: bool = self
+            .rpc
+            .request("evm_revert", [id])
+            .await
+            .map_err(ProviderError::from)?;
+        if !reverted {
+            return Err(HachikoError::Snapshot(format!(
+                "the node has no snapshot {}",
+                id
+            )));
+        }
+
+        self.snapshot().await
+    }
+}
+
+/// A node with `SimpleStorage` deployed from its first account, the chain the tests that
+/// validate against a node share.
+#[cfg(test)]
+pub(crate) struct SimpleStorageChain {
+    pub fixture: ChainFixture,
+    pub address: Address,
+}
+
+#[cfg(test)]
+impl SimpleStorageChain {
+    /// Spawns the node and deploys `SimpleStorage` with "initial value".
+    pub async fn deploy() -> Self {
+        let fixture = ChainFixture::spawn().unwrap();
+        let address = fixture
+            .deploy(
+                "SimpleStorage",
+                &fixture.wallet(0),
+                "initial value".to_string(),
+            )
+            .await
+            .unwrap();
+
+        SimpleStorageChain { fixture, address }
+    }
+
+    /// A validator of the contract, sending from the `index`th account.
+    pub fn validator(&self, index: usize) -> SimpleStorageValidator {
+        let config = self
+            .fixture
+            .validator_config(&self.fixture.wallet(index), self.address);
+
+        SimpleStorageValidator::init_with(config).unwrap()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use ethers::core::abi::Abi;
+
+    use std::fs;
+
+    use crate::{
+        scaffold::ABI_DIR, simple_storage_validator::SimpleStorageInput, validate::validate,
+        Validator,
+    };
+
+    #[test]
+    fn test_abis_match_sources() {
+        let contracts = compile(CONTRACT_SRC_DIR).unwrap();
+
+        // The bindings are generated from the ABIs, which must not drift from the sources
+        for entry in fs::read_dir(ABI_DIR).unwrap() {
+            let path = entry.unwrap().path();
+            let name = path.file_stem().unwrap().to_str().unwrap();
+            let abi: Abi = serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+
+            let contract = contracts
+                .get(name)
+                .unwrap_or_else(|| panic!("{} has no source in {}", name, CONTRACT_SRC_DIR));
+            assert_eq!(contract.abi, abi, "the ABI of {} is out of date", name);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_reset() {
+        let mut chain = SimpleStorageChain::deploy().await;
+        chain.fixture.snapshot().await.unwrap();
+
+        // Every validation starts from the freshly deployed contract
+        for value in ["hi", "bye"].iter() {
+            let mut validator = chain.validator(0);
+            let state = validator.fetch_state().await.unwrap();
+            assert!(format!("{:?}", state).contains("value: \"initial value\""));
+
+            let input = SimpleStorageInput::SetValue(value.to_string());
+            let report = validate(&mut validator, input).await.unwrap();
+            assert!(report.passed(), "{}", report);
+
+            chain.fixture.reset().await.unwrap();
+        }
+
+        let wallet = chain.fixture.wallet(0);
+        assert!(chain.fixture.deploy("Unknown", &wallet, ()).await.is_err());
+    }
+}
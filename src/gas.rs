@@ -0,0 +1,234 @@
+use ethers::core::types::U256;
+
+use std::{collections::BTreeMap, fmt, fs, path::Path};
+
+use crate::HachikoError;
+
+/// File the gas snapshot is written to, relative to the working directory.
+pub const DEFAULT_GAS_SNAPSHOT: &str = ".hachiko-gas.toml";
+
+/// Allowed gas increase over the snapshot, in percent, when none is given.
+pub const DEFAULT_GAS_TOLERANCE: f64 = 0.0;
+
+/// Gas used by every transition of every validator, as stored in a snapshot file.
+///
+/// The file holds a table per validator, mapping each transition to the gas it used:
+///
+/// ```toml
+/// [simple_storage_validator]
+/// setValue = 27142
+/// ```
+///
+/// The gas used by a transition depends on the state it starts from: `setValue` pays more when
+/// `lastSender` goes from zero to non-zero, and little when it stores the value already there.
+/// A snapshot is only comparable with runs starting from the same state, e.g. a freshly deployed
+/// contract or a `ChainFixture` reset to its snapshot.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct GasSnapshot {
+    pub validators: BTreeMap<String, BTreeMap<String, u64>>,
+}
+
+/// A transition that uses more gas than recorded in the snapshot, beyond the tolerance.
+#[derive(Clone, Debug, PartialEq)]
+pub struct GasRegression {
+    pub validator: String,
+    pub transition: String,
+    pub snapshot: u64,
+    pub gas_used: u64,
+}
+
+impl fmt::Display for GasRegression {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let increase = (self.gas_used as f64 - self.snapshot as f64) * 100.0 / self.snapshot as f64;
+        write!(
+            f,
+            "{}::{} used {} gas, {} in snapshot ({:+.2}%)",
+            self.validator, self.transition, self.gas_used, self.snapshot, increase
+        )
+    }
+}
+
+impl GasSnapshot {
+    /// Loads the snapshot at `path`, or an empty snapshot if there is no file yet.
+    pub fn load(path: &str) -> Result<Self, HachikoError> {
+        if !Path::new(path).exists() {
+            return Ok(GasSnapshot::default());
+        }
+
+        let contents = fs::read_to_string(path).map_err(|source| HachikoError::GasSnapshot {
+            path: path.to_string(),
+            source,
+        })?;
+        let validators =
+            toml::from_str(&contents).map_err(|source| HachikoError::InvalidGasSnapshot {
+                path: path.to_string(),
+                source,
+            })?;
+
+        Ok(GasSnapshot { validators })
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), HachikoError> {
+        let contents = toml::to_string(&self.validators)
+            .expect("a gas snapshot only holds tables of integers");
+
+        fs::write(path, contents).map_err(|source| HachikoError::GasSnapshot {
+            path: path.to_string(),
+            source,
+        })
+    }
+
+    /// Records the gas used by a transition, replacing any previous value.
+    pub fn record(&mut self, validator: &str, transition: &str, gas_used: U256) {
+        self.validators
+            .entry(validator.to_string())
+            .or_default()
+            .insert(transition.to_string(), gas_used.as_u64());
+    }
+
+    pub fn get(&self, validator: &str, transition: &str) -> Option<u64> {
+        self.validators.get(validator)?.get(transition).cloned()
+    }
+
+    /// Adds the transitions of `current` that are missing from `self`, or replaces every
+    /// transition of `current` when `overwrite` is set. Returns whether `self` changed.
+    pub fn merge(&mut self, current: &GasSnapshot, overwrite: bool) -> bool {
+        let mut changed = false;
+
+        for (validator, transitions) in current.validators.iter() {
+            let recorded = self.validators.entry(validator.clone()).or_default();
+            for (transition, gas_used) in transitions.iter() {
+                match recorded.get(transition) {
+                    Some(snapshot) if !overwrite || snapshot == gas_used => {}
+                    _ => {
+                        recorded.insert(transition.clone(), *gas_used);
+                        changed = true;
+                    }
+                }
+            }
+        }
+
+        changed
+    }
+
+    /// Lists the transitions of `current` that use more gas than recorded in `self`, by more
+    /// than `tolerance` percent, e.g. `DEFAULT_GAS_TOLERANCE`. Transitions missing from `self`
+    /// are not regressions.
+    pub fn regressions(&self, current: &GasSnapshot, tolerance: f64) -> Vec<GasRegression> {
+        let mut regressions = vec![];
+
+        for (validator, transitions) in current.validators.iter() {
+            for (transition, gas_used) in transitions.iter() {
+                let snapshot = match self.get(validator, transition) {
+                    Some(snapshot) => snapshot,
+                    None => continue,
+                };
+
+                if *gas_used as f64 > snapshot as f64 * (1.0 + tolerance / 100.0) {
+                    regressions.push(GasRegression {
+                        validator: validator.clone(),
+                        transition: transition.clone(),
+                        snapshot,
+                        gas_used: *gas_used,
+                    });
+                }
+            }
+        }
+
+        regressions
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_regressions() {
+        let mut snapshot = GasSnapshot::default();
+        snapshot.record("simple_storage_validator", "setValue", U256::from(10_000));
+
+        let mut current = GasSnapshot::default();
+        current.record("simple_storage_validator", "setValue", U256::from(10_400));
+        current.record(
+            "simple_storage_validator",
+            "newTransition",
+            U256::from(50_000),
+        );
+        assert!(snapshot.regressions(&current, 5.0).is_empty());
+
+        let regressions = snapshot.regressions(&current, 2.0);
+        assert_eq!(
+            regressions,
+            vec![GasRegression {
+                validator: "simple_storage_validator".to_string(),
+                transition: "setValue".to_string(),
+                snapshot: 10_000,
+                gas_used: 10_400,
+            }]
+        );
+        assert_eq!(
+            regressions[0].to_string(),
+            "simple_storage_validator::setValue used 10400 gas, 10000 in snapshot (+4.00%)"
+        );
+
+        // By default, any increase is a regression but using the same gas is not
+        assert_eq!(
+            snapshot.regressions(&current, DEFAULT_GAS_TOLERANCE),
+            regressions
+        );
+        assert!(snapshot
+            .regressions(&snapshot, DEFAULT_GAS_TOLERANCE)
+            .is_empty());
+
+        // A negative tolerance flags transitions that got cheaper, without underflowing
+        current.record("simple_storage_validator", "setValue", U256::from(9_600));
+        let regressions = snapshot.regressions(&current, -5.0);
+        assert_eq!(
+            regressions[0].to_string(),
+            "simple_storage_validator::setValue used 9600 gas, 10000 in snapshot (-4.00%)"
+        );
+    }
+
+    #[test]
+    fn test_merge() {
+        let mut snapshot = GasSnapshot::default();
+        snapshot.record("simple_storage_validator", "setValue", U256::from(10_000));
+
+        let mut current = GasSnapshot::default();
+        current.record("simple_storage_validator", "setValue", U256::from(9_000));
+        assert!(!snapshot.merge(&current, false));
+        assert_eq!(
+            snapshot.get("simple_storage_validator", "setValue"),
+            Some(10_000)
+        );
+
+        current.record(
+            "simple_storage_validator",
+            "newTransition",
+            U256::from(50_000),
+        );
+        assert!(snapshot.merge(&current, false));
+        assert_eq!(
+            snapshot.get("simple_storage_validator", "newTransition"),
+            Some(50_000)
+        );
+
+        assert!(snapshot.merge(&current, true));
+        assert_eq!(snapshot, current);
+        assert!(!snapshot.merge(&current, true));
+    }
+
+    #[test]
+    fn test_snapshot_format() {
+        let mut snapshot = GasSnapshot::default();
+        snapshot.record("simple_storage_validator", "setValue", U256::from(27_142));
+
+        let contents = toml::to_string(&snapshot.validators).unwrap();
+        assert_eq!(contents, "[simple_storage_validator]\nsetValue = 27142\n");
+        assert_eq!(
+            toml::from_str::<BTreeMap<String, BTreeMap<String, u64>>>(&contents).unwrap(),
+            snapshot.validators
+        );
+    }
+}
@@ -7,16 +7,45 @@ extern crate derive_builder;
 use async_trait::async_trait;
 use ethers::{
     contract::ContractError,
-    core::types::{Address, TransactionReceipt, U64},
-    signers::Wallet,
+    core::{
+        abi::{Abi, Detokenize, InvalidOutputType, Token},
+        types::{Address, TransactionReceipt, H256, U256, U64},
+    },
+    providers::Http,
+    signers::{Client, Wallet},
 };
+use rand::Rng;
 use serde::Deserialize;
 
-mod simple_storage;
+mod error;
+pub use error::{HachikoError, ValidationError};
 
-mod simple_storage_validator;
+pub mod events;
 
-mod validate;
+pub mod fixture;
+
+pub mod gas;
+
+pub mod registry;
+
+pub mod report;
+
+pub mod revert;
+
+pub mod runner;
+
+pub mod scaffold;
+
+pub mod scenario;
+
+// Contract bindings generated by the build script from `contract-abi/`, e.g. `simple_storage`
+include!(concat!(env!("OUT_DIR"), "/contracts.rs"));
+
+pub mod simple_storage_validator;
+
+pub mod validate;
+
+pub mod watch;
 
 #[derive(Debug, Deserialize)]
 pub struct FetchConfig {
@@ -42,14 +71,108 @@ impl ValidatorConfig {
     }
 }
 
+/// Directory holding the configuration files of the validators.
+pub const DEFAULT_CONFIG_DIR: &str = "config/default";
+
 pub trait Configurable {
-    fn fetch_config() -> ValidatorConfig;
+    fn fetch_config() -> Result<ValidatorConfig, HachikoError> {
+        Self::fetch_config_from(DEFAULT_CONFIG_DIR)
+    }
+
+    /// Loads the validator's configuration file from `dir`.
+    fn fetch_config_from(dir: &str) -> Result<ValidatorConfig, HachikoError>;
+}
+
+/// A field whose value differs between an expected and an actual state.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FieldDiff {
+    pub field: &'static str,
+    pub expected: String,
+    pub actual: String,
+}
+
+impl std::fmt::Display for FieldDiff {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{}: expected {}, got {}",
+            self.field, self.expected, self.actual
+        )
+    }
 }
 
-pub trait State: Clone + std::fmt::Debug + PartialEq + Sized {
+pub trait StateDiff {
+    /// Lists the fields of `actual` that differ from `self`, the expected state.
+    fn diff(&self, actual: &Self) -> Vec<FieldDiff>;
+}
+
+pub trait State: Clone + std::fmt::Debug + PartialEq + StateDiff + Sized {
     fn get_state(&self) -> Self;
 
     fn get_last_block(&self) -> Option<U64>;
+
+    /// Hash of the block the state was fetched at, used to detect chain reorganizations
+    fn get_last_block_hash(&self) -> Option<H256>;
+}
+
+/// The events emitted by a contract, gathered in one type and told apart by their signature.
+///
+/// Implemented by the enum generated by `#[add_base_state_transition(..)]`.
+pub trait ContractEvents: Detokenize + Sized {
+    /// Signatures of every event in the set
+    fn signatures() -> Vec<H256>;
+
+    fn signature(&self) -> H256;
+
+    /// Decodes the tokens of the event with the given signature.
+    fn decode(signature: H256, tokens: Vec<Token>) -> Result<Self, InvalidOutputType>;
+}
+
+/// A state that can be advanced by the events emitted by its contract.
+pub trait EventSourced<E>: State {
+    /// The state after `event` has been applied to `self`.
+    fn apply(&self, event: &E) -> Self;
+
+    /// Folds `events`, in the order they were emitted, into `self`.
+    fn replay(&self, events: &[E]) -> Self {
+        events
+            .iter()
+            .fold(self.clone(), |state, event| state.apply(event))
+    }
+}
+
+/// A contract-wide property of a state, checked after every sync.
+pub trait Invariant<S>: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    /// Checks `state`, given the previously synced state if there is one.
+    fn check(&self, previous: Option<&S>, state: &S) -> Result<(), String>;
+}
+
+/// Random generation of state transition inputs, used to fuzz a validator.
+pub trait Fuzzable: Sized {
+    fn generate<R: Rng>(rng: &mut R) -> Self;
+
+    /// Simpler candidates for `self`, tried in order while shrinking a failing sequence
+    fn shrink(&self) -> Vec<Self> {
+        vec![]
+    }
+}
+
+/// What a state transition is expected to do.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Expectation<S, E> {
+    /// The transaction succeeds, leaving the contract in `state` after emitting `events`
+    Success { state: S, events: Vec<E> },
+    /// The transaction reverts, with the given reason if any, and leaves the state unchanged
+    Revert { reason: Option<String> },
+}
+
+/// The outcome a state transition was expected to have, along with how its transaction ended.
+#[derive(Clone, Debug)]
+pub struct TransitionOutcome<S, E> {
+    pub expectation: Expectation<S, E>,
+    pub execution: revert::Execution,
 }
 
 pub trait StateTransition: Clone + std::fmt::Debug + PartialEq + Sized {
@@ -57,27 +180,89 @@ pub trait StateTransition: Clone + std::fmt::Debug + PartialEq + Sized {
 }
 
 #[async_trait]
-pub trait Validator<S, T, E>: Sized
+pub trait Validator<S, T, E>: Sized + Send + Sync
 where
     S: State,
     T: StateTransition,
     E: std::fmt::Debug + PartialEq,
 {
+    /// Input to a state transition, e.g. a contract call along with its arguments
+    type Input: Clone + std::fmt::Debug + Default + Send + Sync;
+
     fn get_state(&self) -> S;
 
     fn get_state_transition(&self) -> T;
 
+    /// Name of the transition triggered by `input`, e.g. the contract function it calls
+    fn transition_name(&self, input: &Self::Input) -> &'static str;
+
+    /// Maximum amount of gas the transition triggered by `input` may use
+    fn gas_budget(&self, _input: &Self::Input) -> Option<U256> {
+        None
+    }
+
+    /// Address of the contract the validator sends its transactions to
+    fn address(&self) -> Address;
+
+    /// ABI of the contract, to decode the logs of its transactions
+    fn abi(&self) -> &Abi;
+
+    /// Client the validator sends its transactions and queries through
+    fn client(&self) -> &Client<Http, Wallet>;
+
+    /// Invariants checked against every synced state
+    fn invariants(&self) -> Vec<Box<dyn Invariant<S>>> {
+        vec![]
+    }
+
+    /// Fetches the state at the most recent block.
     async fn fetch_state(&self) -> Result<S, ContractError>;
 
+    /// Fetches the state with every view call pinned to `block`.
+    async fn fetch_state_at(&self, block: U64) -> Result<S, ContractError>;
+
+    /// Fetches the hash of the canonical block at `block`, `None` if the chain is shorter, e.g.
+    /// after a reorganization to a shorter chain.
+    async fn block_hash(&self, block: U64) -> Result<Option<H256>, ContractError> {
+        if block > self.client().get_block_number().await? {
+            return Ok(None);
+        }
+        let block = self.client().get_block(block).await?;
+
+        Ok(block.hash)
+    }
+
+    /// Fetches the recipient of the transaction `tx_hash`, `None` for a contract creation.
+    async fn transaction_recipient(&self, tx_hash: H256) -> Result<Option<Address>, ContractError> {
+        let transaction = self.client().get_transaction(tx_hash).await?;
+
+        Ok(transaction.to)
+    }
+
+    /// Queries the events emitted from block `from` to block `to` (inclusive), in the order
+    /// they were emitted, each along with the number of the block that emitted it.
+    async fn query_events(&self, from: U64, to: U64) -> Result<Vec<(U64, E)>, ContractError>;
+
     async fn sync_state(&mut self) -> Result<S, ContractError>;
 
     async fn sync_events(&mut self, block: U64) -> Result<Vec<E>, ContractError>;
 
-    async fn state_transition(&mut self, initial_state: S) -> Result<(S, Vec<E>), ContractError>;
+    /// Fetches the events emitted from block `from` to block `to` (inclusive), in the order
+    /// they were emitted, and makes them the state transition's event history.
+    async fn sync_events_range(&mut self, from: U64, to: U64) -> Result<Vec<E>, ContractError>;
+
+    /// Sends the transaction for `input` and returns the outcome expected from it.
+    ///
+    /// A reverted transaction is not an error, it is returned through the outcome's `execution`.
+    async fn state_transition(
+        &mut self,
+        initial_state: S,
+        input: Self::Input,
+    ) -> Result<TransitionOutcome<S, E>, ContractError>;
 }
 
-pub trait ValidatorBase {
-    fn init() -> Self;
+pub trait ValidatorBase: Sized {
+    fn init() -> Result<Self, HachikoError>;
 
-    fn init_with(config: ValidatorConfig) -> Self;
+    fn init_with(config: ValidatorConfig) -> Result<Self, HachikoError>;
 }
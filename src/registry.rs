@@ -0,0 +1,124 @@
+use std::{future::Future, pin::Pin};
+
+use crate::{
+    simple_storage_validator::SimpleStorageValidator,
+    validate::ValidationReport,
+    watch::{Divergence, WatchConfig, WatchReport},
+    HachikoError, ValidationError, ValidatorConfig,
+};
+
+pub type ValidateFuture =
+    Pin<Box<dyn Future<Output = Result<ValidationReport, ValidationError>> + Send>>;
+
+pub type WatchFuture = Pin<Box<dyn Future<Output = Result<WatchReport, ValidationError>> + Send>>;
+
+/// Builds a validator from a configuration and runs `watch` against it, reporting every
+/// divergence to the given callback.
+pub type WatchFn = fn(ValidatorConfig, WatchConfig, fn(&Divergence)) -> WatchFuture;
+
+/// A validator registered under a name, with the `Validator<S, T, E>` generics erased.
+#[derive(Clone, Copy)]
+pub struct ValidatorEntry {
+    pub name: &'static str,
+    /// Loads the validator's configuration from a directory
+    pub fetch_config: fn(&str) -> Result<ValidatorConfig, HachikoError>,
+    /// Builds the validator from a configuration and runs `validate` against it
+    pub validate: fn(ValidatorConfig) -> ValidateFuture,
+    /// Runs `watch` against the validator, `None` if it was registered without it
+    pub watch: Option<WatchFn>,
+}
+
+impl ValidatorEntry {
+    /// Configures the validator from `config_dir` and validates it.
+    pub async fn run(&self, config_dir: &str) -> Result<ValidationReport, ValidationError> {
+        let config = (self.fetch_config)(config_dir)?;
+
+        (self.validate)(config).await
+    }
+}
+
+impl std::fmt::Debug for ValidatorEntry {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("ValidatorEntry")
+            .field("name", &self.name)
+            .finish()
+    }
+}
+
+/// Builds a `ValidatorEntry` for a type implementing `Configurable`, `ValidatorBase`
+/// and `Validator`.
+///
+/// Watching applies every event to the state, so it is only wired for validators registered
+/// with a trailing `watch`, whose state implements `EventSourced`:
+///
+/// ```ignore
+/// register_validator!("simple_storage_validator", SimpleStorageValidator, watch)
+/// ```
+#[macro_export]
+macro_rules! register_validator {
+    ($name:expr, $validator:ty) => {
+        $crate::registry::ValidatorEntry {
+            name: $name,
+            fetch_config: <$validator as $crate::Configurable>::fetch_config_from,
+            validate: |config| {
+                Box::pin(async move {
+                    let mut validator = <$validator as $crate::ValidatorBase>::init_with(config)?;
+
+                    $crate::validate::validate(&mut validator, Default::default()).await
+                })
+            },
+            watch: None,
+        }
+    };
+    ($name:expr, $validator:ty, watch) => {
+        $crate::registry::ValidatorEntry {
+            watch: Some(|config, watch_config, on_divergence| {
+                Box::pin(async move {
+                    let mut validator = <$validator as $crate::ValidatorBase>::init_with(config)?;
+
+                    $crate::watch::watch(&mut validator, &watch_config, on_divergence).await
+                })
+            }),
+            ..$crate::register_validator!($name, $validator)
+        }
+    };
+}
+
+/// Every validator compiled into the library.
+///
+/// This is the only place validators are registered: the `list`, `validate` and `watch`
+/// commands all find them here, so a new validator only needs an entry in this list.
+pub fn validators() -> Vec<ValidatorEntry> {
+    vec![register_validator!(
+        "simple_storage_validator",
+        SimpleStorageValidator,
+        watch
+    )]
+}
+
+/// Looks up a registered validator by name.
+pub fn find(name: &str) -> Option<ValidatorEntry> {
+    validators().into_iter().find(|entry| entry.name == name)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_find() {
+        assert!(find("simple_storage_validator").is_some());
+        assert!(find("unknown_validator").is_none());
+    }
+
+    #[test]
+    fn test_register_validator() {
+        let entry = register_validator!("validate_only", SimpleStorageValidator);
+        assert_eq!(entry.name, "validate_only");
+        assert!(entry.watch.is_none());
+
+        let entry = register_validator!("watched", SimpleStorageValidator, watch);
+        assert_eq!(entry.name, "watched");
+        assert!(entry.watch.is_some());
+    }
+}
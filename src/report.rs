@@ -0,0 +1,338 @@
+use serde_json::{json, Value};
+
+use std::{fmt::Write, time::Duration};
+
+use crate::{
+    validate::{Failure, ValidationReport},
+    ValidationError,
+};
+
+/// The validation of one transition of a registered validator, as reported to CI.
+#[derive(Debug)]
+pub struct TestCase {
+    pub validator: &'static str,
+    /// Configuration directory the validator was run with, and hence the node it ran against
+    pub config_dir: String,
+    pub result: Result<ValidationReport, ValidationError>,
+}
+
+impl TestCase {
+    /// Name of the validated transition, or `validate` if the validation did not complete
+    pub fn name(&self) -> &str {
+        match &self.result {
+            Ok(report) => report.transition,
+            Err(_) => "validate",
+        }
+    }
+
+    pub fn passed(&self) -> bool {
+        match &self.result {
+            Ok(report) => report.passed(),
+            Err(_) => false,
+        }
+    }
+
+    pub fn duration(&self) -> Duration {
+        match &self.result {
+            Ok(report) => report.duration(),
+            Err(_) => Duration::default(),
+        }
+    }
+}
+
+/// Number of passed, failed and errored test cases.
+fn count<'a>(cases: impl IntoIterator<Item = &'a TestCase>) -> (usize, usize, usize) {
+    let (mut passed, mut failed, mut errors) = (0, 0, 0);
+    for case in cases {
+        match &case.result {
+            Ok(report) if report.passed() => passed += 1,
+            Ok(_) => failed += 1,
+            Err(_) => errors += 1,
+        }
+    }
+
+    (passed, failed, errors)
+}
+
+fn escape_xml(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            c => escaped.push(c),
+        }
+    }
+
+    escaped
+}
+
+/// The transaction hash, block number and gas of a report, one per line.
+fn receipt_lines(report: &ValidationReport) -> String {
+    let mut lines = String::new();
+    if let Some(tx_hash) = report.tx_hash {
+        writeln!(lines, "tx: {:?}", tx_hash).unwrap();
+    }
+    if let Some(block_number) = report.block_number {
+        writeln!(lines, "block: {}", block_number).unwrap();
+    }
+    if let Some(gas_used) = report.gas_used {
+        writeln!(lines, "gas: {}", gas_used).unwrap();
+    }
+
+    lines
+}
+
+/// Renders the test cases as a JUnit XML document, with one test suite per configuration
+/// directory.
+pub fn junit_xml(cases: &[TestCase]) -> String {
+    let (_, failures, errors) = count(cases);
+    let duration: Duration = cases.iter().map(TestCase::duration).sum();
+
+    let mut xml = String::new();
+    writeln!(xml, r#"<?xml version="1.0" encoding="UTF-8"?>"#).unwrap();
+    writeln!(
+        xml,
+        r#"<testsuites name="hachiko" tests="{}" failures="{}" errors="{}" time="{:.3}">"#,
+        cases.len(),
+        failures,
+        errors,
+        duration.as_secs_f64()
+    )
+    .unwrap();
+
+    let mut config_dirs: Vec<&str> = vec![];
+    for case in cases.iter() {
+        if !config_dirs.contains(&case.config_dir.as_str()) {
+            config_dirs.push(&case.config_dir);
+        }
+    }
+
+    for config_dir in config_dirs {
+        let suite: Vec<&TestCase> = cases
+            .iter()
+            .filter(|case| case.config_dir == config_dir)
+            .collect();
+        let (_, failures, errors) = count(suite.iter().cloned());
+        let duration: Duration = suite.iter().map(|case| case.duration()).sum();
+        writeln!(
+            xml,
+            r#"  <testsuite name="{}" tests="{}" failures="{}" errors="{}" time="{:.3}">"#,
+            escape_xml(config_dir),
+            suite.len(),
+            failures,
+            errors,
+            duration.as_secs_f64()
+        )
+        .unwrap();
+
+        for case in suite {
+            writeln!(
+                xml,
+                r#"    <testcase classname="{}" name="{}" time="{:.3}">"#,
+                escape_xml(case.validator),
+                escape_xml(case.name()),
+                case.duration().as_secs_f64()
+            )
+            .unwrap();
+
+            match &case.result {
+                Ok(report) => {
+                    if let Some(failure) = report.failures.first() {
+                        let details = format!(
+                            "{}expected state: {}\nactual state: {}\n",
+                            report, report.expected_state, report.actual_state
+                        );
+                        writeln!(
+                            xml,
+                            r#"      <failure message="{}">{}</failure>"#,
+                            escape_xml(&failure.to_string()),
+                            escape_xml(&details)
+                        )
+                        .unwrap();
+                    }
+                    writeln!(
+                        xml,
+                        "      <system-out>{}</system-out>",
+                        escape_xml(&receipt_lines(report))
+                    )
+                    .unwrap();
+                }
+                Err(err) => {
+                    writeln!(
+                        xml,
+                        r#"      <error message="{}"/>"#,
+                        escape_xml(&err.to_string())
+                    )
+                    .unwrap();
+                }
+            }
+
+            writeln!(xml, "    </testcase>").unwrap();
+        }
+
+        writeln!(xml, "  </testsuite>").unwrap();
+    }
+
+    writeln!(xml, "</testsuites>").unwrap();
+
+    xml
+}
+
+fn failure_json(failure: &Failure) -> Value {
+    let diffs: Vec<Value> = match failure {
+        Failure::StateMismatch(diffs) => diffs
+            .iter()
+            .map(|diff| {
+                json!({
+                    "field": diff.field,
+                    "expected": diff.expected,
+                    "actual": diff.actual,
+                })
+            })
+            .collect(),
+        _ => vec![],
+    };
+
+    json!({
+        "message": failure.to_string(),
+        "diffs": diffs,
+    })
+}
+
+fn case_json(case: &TestCase) -> Value {
+    match &case.result {
+        Ok(report) => json!({
+            "validator": case.validator,
+            "config_dir": case.config_dir,
+            "transition": case.name(),
+            "status": if report.passed() { "passed" } else { "failed" },
+            "duration_secs": report.duration().as_secs_f64(),
+            "input": report.input,
+            "tx_hash": report.tx_hash.map(|tx_hash| format!("{:?}", tx_hash)),
+            "block_number": report.block_number.map(|block| block.as_u64()),
+            "gas_used": report.gas_used.map(|gas| gas.as_u64()),
+            "expected_state": report.expected_state,
+            "actual_state": report.actual_state,
+            "expected_events": report.expected_events,
+            "actual_events": report.actual_events,
+            "failures": report.failures.iter().map(failure_json).collect::<Vec<_>>(),
+        }),
+        Err(err) => json!({
+            "validator": case.validator,
+            "config_dir": case.config_dir,
+            "transition": case.name(),
+            "status": "error",
+            "duration_secs": 0.0,
+            "error": err.to_string(),
+        }),
+    }
+}
+
+/// Renders the test cases as a JSON document.
+pub fn json_report(cases: &[TestCase]) -> Value {
+    let (passed, failed, errors) = count(cases);
+    let duration: Duration = cases.iter().map(TestCase::duration).sum();
+
+    json!({
+        "tests": cases.len(),
+        "passed": passed,
+        "failed": failed,
+        "errors": errors,
+        "duration_secs": duration.as_secs_f64(),
+        "cases": cases.iter().map(case_json).collect::<Vec<_>>(),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use ethers::core::types::{H256, U256, U64};
+
+    use crate::{validate::StepTiming, FieldDiff};
+
+    fn failed_case() -> TestCase {
+        TestCase {
+            validator: "simple_storage_validator",
+            config_dir: "config/default".to_string(),
+            result: Ok(ValidationReport {
+                transition: "setValue",
+                input: "SetValue(\"<hi>\")".to_string(),
+                expected_state: "value: \"<hi>\"".to_string(),
+                actual_state: "value: \"initial value\"".to_string(),
+                expected_events: vec![],
+                actual_events: vec![],
+                block_number: Some(U64::from(5)),
+                tx_hash: Some(H256::zero()),
+                gas_used: Some(U256::from(27_142)),
+                timings: vec![StepTiming {
+                    step: "state_transition",
+                    duration: Duration::from_millis(1500),
+                }],
+                failures: vec![Failure::StateMismatch(vec![FieldDiff {
+                    field: "value",
+                    expected: "\"<hi>\"".to_string(),
+                    actual: "\"initial value\"".to_string(),
+                }])],
+            }),
+        }
+    }
+
+    fn error_case() -> TestCase {
+        TestCase {
+            validator: "other_validator",
+            config_dir: "config/default".to_string(),
+            result: Err(ValidationError::InvalidScenario("no actors".to_string())),
+        }
+    }
+
+    #[test]
+    fn test_junit_xml() {
+        let xml = junit_xml(&[failed_case(), error_case()]);
+
+        assert!(xml.contains(
+            r#"<testsuites name="hachiko" tests="2" failures="1" errors="1" time="1.500">"#
+        ));
+        assert!(xml.contains(
+            r#"<testsuite name="config/default" tests="2" failures="1" errors="1" time="1.500">"#
+        ));
+        assert!(xml.contains(
+            r#"<testcase classname="simple_storage_validator" name="setValue" time="1.500">"#
+        ));
+        assert!(xml.contains(r#"<failure message="state does not match the expected state">"#));
+        assert!(
+            xml.contains("value: expected &quot;&lt;hi&gt;&quot;, got &quot;initial value&quot;")
+        );
+        assert!(xml.contains("block: 5"));
+        assert!(xml.contains("gas: 27142"));
+        assert!(
+            xml.contains(r#"<testcase classname="other_validator" name="validate" time="0.000">"#)
+        );
+        assert!(xml.contains(r#"<error message="invalid scenario: no actors"/>"#));
+    }
+
+    #[test]
+    fn test_json_report() {
+        let report = json_report(&[failed_case(), error_case()]);
+
+        assert_eq!(report["tests"], 2);
+        assert_eq!(report["failed"], 1);
+        assert_eq!(report["errors"], 1);
+
+        let case = &report["cases"][0];
+        assert_eq!(case["status"], "failed");
+        assert_eq!(case["block_number"], 5);
+        assert_eq!(case["gas_used"], 27_142);
+        assert_eq!(case["failures"][0]["diffs"][0]["field"], "value");
+        assert_eq!(
+            case["failures"][0]["diffs"][0]["actual"],
+            "\"initial value\""
+        );
+
+        assert_eq!(report["cases"][1]["status"], "error");
+        assert_eq!(report["cases"][1]["error"], "invalid scenario: no actors");
+    }
+}
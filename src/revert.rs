@@ -0,0 +1,274 @@
+use ethers::{
+    contract::ContractError,
+    core::{
+        abi::{self, ParamType, Token},
+        types::{BlockNumber, TransactionReceipt, TransactionRequest, U64},
+    },
+    providers::{Http, Provider},
+};
+
+/// Selector of `Error(string)`, the revert data emitted by `revert("reason")` and `require`.
+pub const ERROR_SELECTOR: [u8; 4] = [0x08, 0xc3, 0x79, 0xa0];
+
+/// How the transaction of a state transition ended.
+#[derive(Clone, Debug)]
+pub enum Execution {
+    /// The transaction was mined and succeeded
+    Mined(TransactionReceipt),
+    /// The transaction reverted, either when sent or once mined with status 0
+    Reverted {
+        reason: Option<String>,
+        receipt: Option<TransactionReceipt>,
+    },
+}
+
+impl Execution {
+    /// Classifies the result of sending a transaction and waiting for its receipt.
+    ///
+    /// Errors other than a revert are returned as is.
+    pub fn capture(
+        result: Result<TransactionReceipt, ContractError>,
+    ) -> Result<Self, ContractError> {
+        match result {
+            Ok(receipt) if receipt.status == Some(U64::zero()) => Ok(Execution::Reverted {
+                reason: None,
+                receipt: Some(receipt),
+            }),
+            Ok(receipt) => Ok(Execution::Mined(receipt)),
+            Err(err) => {
+                let message = err.to_string();
+                if is_revert(&message) {
+                    Ok(Execution::Reverted {
+                        reason: revert_reason(&message),
+                        receipt: None,
+                    })
+                } else {
+                    Err(err)
+                }
+            }
+        }
+    }
+
+    /// Recovers the reason of a transaction that was mined but reverted, which its receipt does
+    /// not record, by replaying it with `eth_call`. Other executions are returned as they are.
+    pub async fn recover_reason(self, provider: &Provider<Http>) -> Result<Self, ContractError> {
+        match self {
+            Execution::Reverted {
+                reason: None,
+                receipt: Some(receipt),
+            } => Ok(Execution::Reverted {
+                reason: replay_reason(provider, &receipt).await?,
+                receipt: Some(receipt),
+            }),
+            execution => Ok(execution),
+        }
+    }
+
+    pub fn receipt(&self) -> Option<&TransactionReceipt> {
+        match self {
+            Execution::Mined(receipt) => Some(receipt),
+            Execution::Reverted { receipt, .. } => receipt.as_ref(),
+        }
+    }
+}
+
+/// Replays the transaction of `receipt` with `eth_call` on the state of the block before its
+/// own, and returns the reason it reverts with.
+///
+/// The replay starts from the same state as the transaction as long as it was the first of its
+/// block, as on a node mining a block per transaction.
+async fn replay_reason(
+    provider: &Provider<Http>,
+    receipt: &TransactionReceipt,
+) -> Result<Option<String>, ContractError> {
+    let block = match receipt.block_number {
+        Some(block) if !block.is_zero() => block - 1,
+        _ => return Ok(None),
+    };
+
+    // 1. Rebuild the call from the mined transaction
+    let tx = provider.get_transaction(receipt.transaction_hash).await?;
+    let mut call = TransactionRequest::new()
+        .from(tx.from)
+        .value(tx.value)
+        .gas(tx.gas)
+        .gas_price(tx.gas_price)
+        .data(tx.input);
+    if let Some(to) = tx.to {
+        call = call.to(to);
+    }
+
+    // 2. The reason is either returned as revert data or in the node's error. A call failing
+    //    for another reason, e.g. running out of gas, has no reason to recover.
+    match provider.call(&call, Some(BlockNumber::Number(block))).await {
+        Ok(data) => Ok(decode_revert_data(&data.0)),
+        Err(err) => Ok(revert_reason(&err.to_string())),
+    }
+}
+
+/// Whether a node error reports a reverted transaction.
+pub fn is_revert(message: &str) -> bool {
+    message.to_lowercase().contains("revert")
+}
+
+/// Decodes the reason from `Error(string)` revert data.
+pub fn decode_revert_data(data: &[u8]) -> Option<String> {
+    if data.len() < 4 || data[..4] != ERROR_SELECTOR {
+        return None;
+    }
+
+    match abi::decode(&[ParamType::String], &data[4..]).ok()?.pop()? {
+        Token::String(reason) => Some(reason),
+        _ => None,
+    }
+}
+
+/// Byte index of the first occurrence of `needle` in `haystack`, ignoring ASCII case.
+///
+/// Unlike searching a lowercased copy, the index always points into `haystack` itself, whose
+/// non-ASCII characters may not lowercase to the same number of bytes.
+fn find_ignore_ascii_case(haystack: &str, needle: &str) -> Option<usize> {
+    haystack
+        .char_indices()
+        .map(|(index, _)| index)
+        .find(|index| {
+            haystack[*index..]
+                .get(..needle.len())
+                .is_some_and(|candidate| candidate.eq_ignore_ascii_case(needle))
+        })
+}
+
+/// Extracts the revert reason from a node error message.
+///
+/// The `Error(string)` revert data is decoded when the node includes it in the message, as
+/// geth does. Otherwise the reason is read from the text following `revert`, as in ganache's
+/// "VM Exception while processing transaction: revert <reason>".
+pub fn revert_reason(message: &str) -> Option<String> {
+    let selector = format!("0x{}", hex::encode(ERROR_SELECTOR));
+    if let Some(start) = message.find(selector.as_str()) {
+        let data: String = message[start + 2..]
+            .chars()
+            .take_while(|c| c.is_ascii_hexdigit())
+            .collect();
+        if let Some(reason) = hex::decode(data)
+            .ok()
+            .and_then(|data| decode_revert_data(&data))
+        {
+            return Some(reason);
+        }
+    }
+
+    // JSON-RPC errors are displayed as "(code: .., message: .., data: ..)"
+    let message = message.split(", data:").next().unwrap_or_default();
+    let start = find_ignore_ascii_case(message, "revert")?;
+    let reason = message[start..]
+        .trim_start_matches(|c: char| c.is_alphabetic())
+        .trim_start_matches(':')
+        .trim();
+
+    match reason {
+        "" => None,
+        reason => Some(reason.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use ethers::utils::id;
+
+    use std::convert::TryFrom;
+
+    use crate::fixture::ChainFixture;
+
+    // Error(string) data for "not the owner"
+    const REVERT_DATA: &str = "08c379a0\
+        0000000000000000000000000000000000000000000000000000000000000020\
+        000000000000000000000000000000000000000000000000000000000000000d\
+        6e6f7420746865206f776e657200000000000000000000000000000000000000";
+
+    #[test]
+    fn test_decode_revert_data() {
+        let data = hex::decode(REVERT_DATA).unwrap();
+        assert_eq!(decode_revert_data(&data), Some("not the owner".to_string()));
+        assert_eq!(decode_revert_data(&data[4..]), None);
+    }
+
+    #[test]
+    fn test_revert_reason() {
+        assert_eq!(
+            revert_reason("VM Exception while processing transaction: revert not the owner"),
+            Some("not the owner".to_string())
+        );
+        assert_eq!(
+            revert_reason("execution reverted: not the owner"),
+            Some("not the owner".to_string())
+        );
+        assert_eq!(
+            revert_reason(&format!("execution reverted, data: 0x{}", REVERT_DATA)),
+            Some("not the owner".to_string())
+        );
+        assert_eq!(
+            revert_reason(
+                "(code: -32000, message: VM Exception while processing transaction: \
+                revert reverted twice, data: None)"
+            ),
+            Some("reverted twice".to_string())
+        );
+        assert_eq!(
+            revert_reason("VM Exception while processing transaction: revert"),
+            None
+        );
+
+        // Characters whose lowercase form is longer must not shift the reason
+        assert_eq!(
+            revert_reason("İİİİİİİİ: REVERT ünicode reason"),
+            Some("ünicode reason".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_recover_reason() {
+        let fixture = ChainFixture::spawn().unwrap();
+        let wallet = fixture.wallet(0);
+        let address = fixture.deploy("Guarded", &wallet, ()).await.unwrap();
+        let provider = Provider::<Http>::try_from(fixture.endpoint()).unwrap();
+        let client = wallet.connect(Provider::<Http>::try_from(fixture.endpoint()).unwrap());
+
+        // 1. Send a transaction that reverts, with its gas set so that it is mined rather than
+        //    rejected while estimating gas
+        let data = [
+            &id("increment(bool)")[..],
+            &abi::encode(&[Token::Bool(false)]),
+        ]
+        .concat();
+        let tx = TransactionRequest::new()
+            .to(address)
+            .data(data)
+            .gas(100_000);
+        let _ = client.send_transaction(tx, None).await;
+
+        // 2. Its receipt only records that it failed
+        let block = provider.get_block_number().await.unwrap();
+        let tx_hash = provider.get_block(block).await.unwrap().transactions[0];
+        let receipt = provider.get_transaction_receipt(tx_hash).await.unwrap();
+        let execution = Execution::capture(Ok(receipt)).unwrap();
+        assert!(matches!(
+            execution,
+            Execution::Reverted { reason: None, .. }
+        ));
+
+        // 3. Replaying it recovers the reason
+        match execution.recover_reason(&provider).await.unwrap() {
+            Execution::Reverted {
+                reason,
+                receipt: Some(receipt),
+            } => {
+                assert_eq!(reason.as_deref(), Some("not allowed"));
+                assert_eq!(receipt.transaction_hash, tx_hash);
+            }
+            execution => panic!("expected a mined revert, got {:?}", execution),
+        }
+    }
+}
@@ -0,0 +1,235 @@
+use ethers::core::types::Address;
+use futures::{
+    channel::mpsc,
+    join,
+    stream::{self, StreamExt},
+};
+
+use crate::{registry::ValidatorEntry, report::TestCase, ValidatorConfig};
+
+/// Parameters of a `run_all` run.
+#[derive(Clone, Debug)]
+pub struct RunnerConfig {
+    /// Maximum number of validators running at the same time
+    pub concurrency: usize,
+}
+
+impl Default for RunnerConfig {
+    fn default() -> Self {
+        RunnerConfig { concurrency: 4 }
+    }
+}
+
+/// A registered validator along with the configuration it runs with.
+struct Job {
+    /// Position of the job in the order the validators were given
+    index: usize,
+    entry: ValidatorEntry,
+    config_dir: String,
+    config: ValidatorConfig,
+}
+
+/// Jobs that send transactions from the same account to the same node.
+///
+/// The nonce of a transaction is read from the node when it is sent, so two validators sending
+/// from one account at the same time could both use the same nonce. The jobs of a queue are run
+/// one after the other instead.
+struct AccountQueue {
+    url: String,
+    sender: Address,
+    jobs: Vec<Job>,
+}
+
+fn enqueue(queues: &mut Vec<AccountQueue>, job: Job) {
+    let url = job.config.url.clone();
+    let sender = job.config.wallet.address();
+
+    match queues
+        .iter_mut()
+        .find(|queue| queue.url == url && queue.sender == sender)
+    {
+        Some(queue) => queue.jobs.push(job),
+        None => queues.push(AccountQueue {
+            url,
+            sender,
+            jobs: vec![job],
+        }),
+    }
+}
+
+/// Validates every entry with the configuration of every directory in `config_dirs`, each
+/// directory usually pointing to another node.
+///
+/// Up to `concurrency` accounts send transactions at the same time, while the validators sharing
+/// an account on a node run one after the other. `on_case` is called as soon as a validation
+/// completes, and the test cases are returned in the order of `config_dirs` then `entries`.
+pub async fn run_all<F>(
+    entries: &[ValidatorEntry],
+    config_dirs: &[String],
+    config: &RunnerConfig,
+    mut on_case: F,
+) -> Vec<TestCase>
+where
+    F: FnMut(&TestCase),
+{
+    let mut cases = vec![];
+    let mut queues = vec![];
+
+    // 1. Load the configuration of every validator, and queue it by account
+    let jobs = config_dirs
+        .iter()
+        .flat_map(|config_dir| entries.iter().map(move |entry| (config_dir, *entry)));
+    for (index, (config_dir, entry)) in jobs.enumerate() {
+        match (entry.fetch_config)(config_dir) {
+            Ok(config) => enqueue(
+                &mut queues,
+                Job {
+                    index,
+                    entry,
+                    config_dir: config_dir.clone(),
+                    config,
+                },
+            ),
+            Err(err) => {
+                let case = TestCase {
+                    validator: entry.name,
+                    config_dir: config_dir.clone(),
+                    result: Err(err.into()),
+                };
+                on_case(&case);
+                cases.push((index, case));
+            }
+        }
+    }
+
+    // 2. Run the queues concurrently, and the jobs of a queue one after the other, sending every
+    //    test case as soon as its job completes
+    let (sender, mut receiver) = mpsc::unbounded();
+    let run_queues = async move {
+        stream::iter(queues)
+            .for_each_concurrent(config.concurrency.max(1), |queue| {
+                let sender = sender.clone();
+                async move {
+                    for job in queue.jobs {
+                        let result = (job.entry.validate)(job.config).await;
+                        let case = TestCase {
+                            validator: job.entry.name,
+                            config_dir: job.config_dir,
+                            result,
+                        };
+                        sender
+                            .unbounded_send((job.index, case))
+                            .expect("the test cases are received until every queue is done");
+                    }
+                }
+            })
+            .await
+    };
+
+    // 3. Report the test cases while the queues run, until every sender is dropped
+    let report_cases = async {
+        while let Some((index, case)) = receiver.next().await {
+            on_case(&case);
+            cases.push((index, case));
+        }
+    };
+    join!(run_queues, report_cases);
+
+    // 4. Aggregate the test cases in the order the validators were given
+    cases.sort_by_key(|(index, _)| *index);
+    cases.into_iter().map(|(_, case)| case).collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use tokio::time::{delay_for, timeout};
+
+    use std::{
+        sync::atomic::{AtomicBool, Ordering},
+        time::Duration,
+    };
+
+    use crate::{
+        registry::{self, ValidateFuture},
+        simple_storage_validator::SimpleStorageValidator,
+        Configurable, ValidationError,
+    };
+
+    fn job(index: usize, url: &str) -> Job {
+        let mut config = SimpleStorageValidator::fetch_config().unwrap();
+        config.url = url.to_string();
+
+        Job {
+            index,
+            entry: registry::find("simple_storage_validator").unwrap(),
+            config_dir: "config/default".to_string(),
+            config,
+        }
+    }
+
+    #[test]
+    fn test_enqueue() {
+        let mut queues = vec![];
+        enqueue(&mut queues, job(0, "http://localhost:8545"));
+        enqueue(&mut queues, job(1, "http://localhost:8546"));
+        enqueue(&mut queues, job(2, "http://localhost:8545"));
+
+        assert_eq!(queues.len(), 2);
+        let indices: Vec<Vec<usize>> = queues
+            .iter()
+            .map(|queue| queue.jobs.iter().map(|job| job.index).collect())
+            .collect();
+        assert_eq!(indices, vec![vec![0, 2], vec![1]]);
+    }
+
+    static FIRST_CASE_REPORTED: AtomicBool = AtomicBool::new(false);
+
+    fn validate_first(_config: ValidatorConfig) -> ValidateFuture {
+        Box::pin(async { Err(ValidationError::InvalidScenario("first".to_string())) })
+    }
+
+    /// Only completes once the test case of `validate_first` was reported.
+    fn validate_second(_config: ValidatorConfig) -> ValidateFuture {
+        Box::pin(async {
+            while !FIRST_CASE_REPORTED.load(Ordering::SeqCst) {
+                delay_for(Duration::from_millis(1)).await;
+            }
+
+            Err(ValidationError::InvalidScenario("second".to_string()))
+        })
+    }
+
+    #[tokio::test]
+    async fn test_run_all_reports_each_job() {
+        // Both validators send from the same account, so they run in the same queue
+        let entry = registry::find("simple_storage_validator").unwrap();
+        let entries = vec![
+            ValidatorEntry {
+                name: "first",
+                validate: validate_first,
+                ..entry
+            },
+            ValidatorEntry {
+                name: "second",
+                validate: validate_second,
+                ..entry
+            },
+        ];
+        let config_dirs = vec!["config/default".to_string()];
+
+        let mut reported = vec![];
+        let run = run_all(&entries, &config_dirs, &RunnerConfig::default(), |case| {
+            FIRST_CASE_REPORTED.store(true, Ordering::SeqCst);
+            reported.push(case.validator);
+        });
+        let cases = timeout(Duration::from_secs(5), run)
+            .await
+            .expect("the first test case should be reported before the queue is done");
+
+        assert_eq!(reported, vec!["first", "second"]);
+        let validators: Vec<&str> = cases.iter().map(|case| case.validator).collect();
+        assert_eq!(validators, vec!["first", "second"]);
+    }
+}
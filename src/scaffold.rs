@@ -0,0 +1,777 @@
+use inflector::cases::{pascalcase::to_pascal_case, snakecase::to_snake_case};
+use serde_json::Value;
+
+use std::{fmt::Write, fs, path::Path, process::Command};
+
+use crate::{HachikoError, DEFAULT_CONFIG_DIR};
+
+/// Directory the validator modules are written to.
+pub const SRC_DIR: &str = "src";
+
+/// Directory of the contract ABIs, whose bindings are generated by the build script.
+pub const ABI_DIR: &str = "contract-abi";
+
+/// Name of the modules generated for a contract, e.g. `simple_storage` for `SimpleStorage`.
+pub fn module_name(name: &str) -> String {
+    to_snake_case(name)
+}
+
+/// A parameter of a function or event, with its Rust type.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AbiParam {
+    pub name: String,
+    pub rust_type: String,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct AbiFunction {
+    pub name: String,
+    pub inputs: Vec<AbiParam>,
+    /// Rust type returned by a call, a tuple when there are several outputs
+    pub output: String,
+    /// Whether the function is `view` or `pure`
+    pub view: bool,
+}
+
+/// The parts of a contract's ABI a validator is scaffolded from.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ContractAbi {
+    pub functions: Vec<AbiFunction>,
+    pub events: Vec<String>,
+}
+
+impl ContractAbi {
+    /// Parameterless view functions, each read into one field of the state
+    pub fn state_functions(&self) -> impl Iterator<Item = &AbiFunction> {
+        self.functions
+            .iter()
+            .filter(|function| function.view && function.inputs.is_empty())
+    }
+
+    /// Functions that transition the contract's state
+    pub fn transition_functions(&self) -> impl Iterator<Item = &AbiFunction> {
+        self.functions.iter().filter(|function| !function.view)
+    }
+}
+
+/// The Rust type `abigen` maps a Solidity type to.
+pub fn rust_type(solidity_type: &str) -> Result<String, String> {
+    if let Some(element) = solidity_type.strip_suffix("[]") {
+        return Ok(format!("Vec<{}>", rust_type(element)?));
+    }
+    if let Some(element) = solidity_type.strip_suffix(']') {
+        let open = element.rfind('[').ok_or("unbalanced array brackets")?;
+        return Ok(format!(
+            "[{}; {}]",
+            rust_type(&element[..open])?,
+            &element[open + 1..]
+        ));
+    }
+
+    let rust_type = match solidity_type {
+        "address" => "Address".to_string(),
+        "bool" => "bool".to_string(),
+        "string" => "String".to_string(),
+        "bytes" => "Vec<u8>".to_string(),
+        "int" => "I256".to_string(),
+        "uint" => "U256".to_string(),
+        _ => {
+            let (prefix, size) = match solidity_type {
+                t if t.starts_with("uint") => ("uint", &t[4..]),
+                t if t.starts_with("int") => ("int", &t[3..]),
+                t if t.starts_with("bytes") => ("bytes", &t[5..]),
+                t => return Err(format!("unsupported type {}", t)),
+            };
+            let size: usize = size
+                .parse()
+                .map_err(|_| format!("unsupported type {}", solidity_type))?;
+
+            match (prefix, size) {
+                ("bytes", size) => format!("[u8; {}]", size),
+                ("uint", 8) => "u8".to_string(),
+                ("uint", 16) => "u16".to_string(),
+                ("uint", 17..=32) => "u32".to_string(),
+                ("uint", 33..=64) => "u64".to_string(),
+                ("uint", 65..=128) => "u128".to_string(),
+                ("uint", _) => "U256".to_string(),
+                ("int", 8) => "i8".to_string(),
+                ("int", 16) => "i16".to_string(),
+                ("int", 17..=32) => "i32".to_string(),
+                ("int", 33..=64) => "i64".to_string(),
+                ("int", 65..=128) => "i128".to_string(),
+                (_, _) => "I256".to_string(),
+            }
+        }
+    };
+
+    Ok(rust_type)
+}
+
+fn parse_params(params: Option<&Value>) -> Result<Vec<AbiParam>, String> {
+    let params = match params.and_then(Value::as_array) {
+        Some(params) => params,
+        None => return Ok(vec![]),
+    };
+
+    params
+        .iter()
+        .map(|param| {
+            let solidity_type = param["type"].as_str().ok_or("parameter without a type")?;
+            Ok(AbiParam {
+                name: param["name"].as_str().unwrap_or_default().to_string(),
+                rust_type: rust_type(solidity_type)?,
+            })
+        })
+        .collect()
+}
+
+/// Reads the functions and events of an ABI JSON document.
+///
+/// Overloaded functions and events are only kept once, as `abigen` does not tell them apart.
+pub fn parse_abi(json: &str) -> Result<ContractAbi, String> {
+    let items: Vec<Value> = serde_json::from_str(json).map_err(|err| err.to_string())?;
+    let mut abi = ContractAbi {
+        functions: vec![],
+        events: vec![],
+    };
+
+    for item in items.iter() {
+        let name = item["name"].as_str().unwrap_or_default().to_string();
+        match item["type"].as_str() {
+            Some("function") => {
+                if abi.functions.iter().any(|function| function.name == name) {
+                    continue;
+                }
+
+                let outputs = parse_params(item.get("outputs"))?;
+                let output = match outputs.as_slice() {
+                    [] => "()".to_string(),
+                    [output] => output.rust_type.clone(),
+                    outputs => {
+                        let types: Vec<&str> = outputs
+                            .iter()
+                            .map(|output| output.rust_type.as_str())
+                            .collect();
+                        format!("({})", types.join(", "))
+                    }
+                };
+                let view = match item["stateMutability"].as_str() {
+                    Some(mutability) => mutability == "view" || mutability == "pure",
+                    None => item["constant"].as_bool().unwrap_or_default(),
+                };
+
+                abi.functions.push(AbiFunction {
+                    inputs: parse_params(item.get("inputs"))?,
+                    name,
+                    output,
+                    view,
+                });
+            }
+            Some("event") if !abi.events.contains(&name) => abi.events.push(name),
+            _ => {}
+        }
+    }
+
+    Ok(abi)
+}
+
+/// Fields added to every state by `#[add_base_state]`.
+const BASE_STATE_FIELDS: [&str; 2] = ["last_block", "last_block_hash"];
+
+/// Name of the state field holding the result of a view function, e.g. `value` for `getValue`.
+fn field_name(function: &str) -> String {
+    let name = to_snake_case(function);
+    match name.strip_prefix("get_") {
+        Some(field) if !field.is_empty() => field.to_string(),
+        _ => name,
+    }
+}
+
+/// Names given to the arguments of a transition input, in order.
+fn argument_names(function: &AbiFunction) -> Vec<String> {
+    function
+        .inputs
+        .iter()
+        .enumerate()
+        .map(|(i, input)| match input.name.trim_start_matches('_') {
+            "" => format!("arg{}", i),
+            name => to_snake_case(name),
+        })
+        .collect()
+}
+
+/// The `ethers::core::types` used by the generated validator.
+fn type_imports(abi: &ContractAbi) -> String {
+    let mut types = vec![
+        "Address",
+        "Filter",
+        "PrivateKey",
+        "TransactionReceipt",
+        "ValueOrArray",
+        "H256",
+    ];
+    let used: Vec<&str> = abi
+        .state_functions()
+        .map(|function| function.output.as_str())
+        .chain(
+            abi.transition_functions()
+                .flat_map(|function| function.inputs.iter())
+                .map(|input| input.rust_type.as_str()),
+        )
+        .collect();
+    for extra in ["I256", "U256"].iter() {
+        if used.iter().any(|rust_type| rust_type.contains(extra)) {
+            types.push(extra);
+        }
+    }
+    types.push("U64");
+
+    types.join(", ")
+}
+
+/// Generates the module holding the state, transition, input and validator of a contract.
+pub fn validator_module(name: &str, abi: &ContractAbi) -> Result<String, String> {
+    let module = module_name(name);
+    if abi.events.is_empty() {
+        return Err("the ABI has no events".to_string());
+    }
+    if abi.transition_functions().next().is_none() {
+        return Err("the ABI has no functions that transition the state".to_string());
+    }
+
+    let filters: Vec<String> = abi
+        .events
+        .iter()
+        .map(|event| format!("{}Filter", event))
+        .collect();
+
+    // Functions such as `getValue` and `value` would both be read into `value`
+    let mut fields: Vec<(String, &str)> = vec![];
+    for function in abi.state_functions() {
+        let field = field_name(&function.name);
+        if BASE_STATE_FIELDS.contains(&field.as_str()) {
+            return Err(format!(
+                "`{}` would be read into the state field `{}`, which holds the synced block",
+                function.name, field
+            ));
+        }
+        if let Some((_, other)) = fields.iter().find(|(name, _)| *name == field) {
+            return Err(format!(
+                "`{}` and `{}` would both be read into the state field `{}`",
+                other, function.name, field
+            ));
+        }
+        fields.push((field, &function.name));
+    }
+
+    let mut state_fields = String::new();
+    for function in abi.state_functions() {
+        writeln!(
+            state_fields,
+            "    #[view({})]",
+            to_snake_case(&function.name)
+        )
+        .unwrap();
+        writeln!(
+            state_fields,
+            "    {}: {},",
+            field_name(&function.name),
+            function.output
+        )
+        .unwrap();
+    }
+
+    let mut apply_arms = String::new();
+    for event in abi.events.iter() {
+        writeln!(
+            apply_arms,
+            "            {}Event::{}(_event) => {{}}",
+            name, event
+        )
+        .unwrap();
+    }
+
+    let mut input_variants = String::new();
+    let mut name_arms = String::new();
+    let mut send_arms = String::new();
+    let mut expectation_arms = String::new();
+    for function in abi.transition_functions() {
+        let variant = to_pascal_case(&function.name);
+        let arguments = argument_names(function);
+        let types: Vec<&str> = function
+            .inputs
+            .iter()
+            .map(|input| input.rust_type.as_str())
+            .collect();
+
+        if types.is_empty() {
+            writeln!(input_variants, "    {},", variant).unwrap();
+            writeln!(
+                send_arms,
+                "            {}Input::{} => self.contract.{}().send().await,",
+                name,
+                variant,
+                to_snake_case(&function.name)
+            )
+            .unwrap();
+        } else {
+            writeln!(input_variants, "    {}({}),", variant, types.join(", ")).unwrap();
+            let cloned: Vec<String> = arguments
+                .iter()
+                .map(|argument| format!("{}.clone()", argument))
+                .collect();
+            writeln!(
+                send_arms,
+                "            {}Input::{}({}) => {{\n                self.contract.{}({}).send().await\n            }}",
+                name,
+                variant,
+                arguments.join(", "),
+                to_snake_case(&function.name),
+                cloned.join(", ")
+            )
+            .unwrap();
+        }
+
+        let pattern = if types.is_empty() { "" } else { "(..)" };
+        writeln!(
+            name_arms,
+            "            {}Input::{}{} => \"{}\",",
+            name, variant, pattern, function.name
+        )
+        .unwrap();
+        writeln!(
+            expectation_arms,
+            "            // TODO: build the state and events expected after `{}`\n            {}Input::{}{} => Expectation::Success {{\n                state: self.state.clone(),\n                events: vec![],\n            }},",
+            function.name, name, variant, pattern
+        )
+        .unwrap();
+    }
+
+    let default_input = {
+        let function = abi
+            .transition_functions()
+            .next()
+            .expect("the ABI has transition functions");
+        let variant = to_pascal_case(&function.name);
+        match function.inputs.len() {
+            0 => format!("{}Input::{}", name, variant),
+            arity => format!(
+                "{}Input::{}({})",
+                name,
+                variant,
+                vec!["Default::default()"; arity].join(", ")
+            ),
+        }
+    };
+
+    Ok(VALIDATOR_TEMPLATE
+        .replace("{types}", &type_imports(abi))
+        .replace("{module}", &module)
+        .replace("{filters}", &filters.join(", "))
+        .replace("{state_fields}", &state_fields)
+        .replace("{apply_arms}", &apply_arms)
+        .replace("{input_variants}", &input_variants)
+        .replace("{default_input}", &default_input)
+        .replace("{name_arms}", &name_arms)
+        .replace("{send_arms}", &send_arms)
+        .replace("{expectation_arms}", &expectation_arms)
+        .replace("{Name}", name))
+}
+
+/// Generates the configuration file of a validator, to be filled in with a node and an account.
+pub fn config_toml() -> String {
+    CONFIG_TEMPLATE.to_string()
+}
+
+/// Adds the ABI, validator module and configuration of a new validator for the contract whose
+/// ABI is at `abi_path`, and returns the paths of the files written.
+///
+/// The ABI is copied to `contract-abi/`, where the build script generates its bindings. Nothing
+/// is written if any of the files already exists. The generated module is formatted with
+/// `rustfmt` when it is installed.
+pub fn scaffold(name: &str, abi_path: &str) -> Result<Vec<String>, HachikoError> {
+    let invalid_abi = |reason: String| HachikoError::InvalidAbi {
+        path: abi_path.to_string(),
+        reason,
+    };
+
+    let json = fs::read_to_string(abi_path).map_err(|source| HachikoError::ReadAbi {
+        path: abi_path.to_string(),
+        source,
+    })?;
+    let abi = parse_abi(&json).map_err(invalid_abi)?;
+
+    let module = module_name(name);
+    let abi_copy_path = format!("{}/{}.json", ABI_DIR, name);
+    let validator_path = format!("{}/{}_validator.rs", SRC_DIR, module);
+    let config_path = format!("{}/{}_validator.toml", DEFAULT_CONFIG_DIR, module);
+
+    // The ABI is left as is when it already is in `contract-abi/`
+    let abi_in_place = match (fs::canonicalize(abi_path), fs::canonicalize(&abi_copy_path)) {
+        (Ok(path), Ok(copy_path)) => path == copy_path,
+        _ => false,
+    };
+    let mut files = vec![];
+    if !abi_in_place {
+        files.push((abi_copy_path, json));
+    }
+    files.push((
+        validator_path,
+        validator_module(name, &abi).map_err(invalid_abi)?,
+    ));
+    files.push((config_path, config_toml()));
+
+    if let Some((path, _)) = files.iter().find(|(path, _)| Path::new(path).exists()) {
+        return Err(HachikoError::FileExists(path.clone()));
+    }
+
+    // 1. Write the ABI, the validator module and its configuration
+    for (path, contents) in files.iter() {
+        fs::write(path, contents).map_err(|source| HachikoError::WriteFile {
+            path: path.clone(),
+            source,
+        })?;
+    }
+    let paths: Vec<String> = files.into_iter().map(|(path, _)| path).collect();
+
+    // 2. Format the generated module, leaving it as generated if rustfmt is missing
+    for path in paths.iter().filter(|path| path.ends_with(".rs")) {
+        let _ = Command::new("rustfmt")
+            .args(["--edition", "2018", path])
+            .status();
+    }
+
+    Ok(paths)
+}
+
+const CONFIG_TEMPLATE: &str = r#"# Private key of the account sending the transitions, as hex without the 0x prefix
+private_key = ""
+# Address of the deployed contract, as hex without the 0x prefix
+address = ""
+url = "http://localhost:8545"
+"#;
+
+const VALIDATOR_TEMPLATE: &str = r#"use async_trait::async_trait;
+use config::{Config, File};
+use ethers::{
+    contract::ContractError,
+    core::{
+        abi::{Abi, Detokenize, InvalidOutputType, Token},
+        types::{{types}},
+    },
+    providers::{Http, Provider},
+    signers::{Client, Wallet},
+};
+use validator_derive::{
+    add_base_state, add_base_state_transition, BaseState, BaseStateTransition, Configurable,
+    FetchState, ValidatorBase,
+};
+
+use std::{convert::TryFrom, str::FromStr, sync::Arc, time::Duration};
+
+use crate::{
+    events::{decode_event, query_range, sort_logs, DEFAULT_CHUNK_SIZE},
+    revert::Execution,
+    {module}::{{Name}, {filters}},
+    Configurable, ContractEvents, EventSourced, Expectation, FetchConfig, FieldDiff, HachikoError,
+    State, StateDiff, StateTransition, TransitionOutcome, Validator, ValidatorBase,
+    ValidatorConfig,
+};
+
+#[add_base_state]
+#[derive(BaseState, FetchState, Clone, Debug, Default, Builder)]
+#[hachiko(contract = "{Name}<Http, Wallet>")]
+pub struct {Name}State {
+{state_fields}}
+
+#[add_base_state_transition({filters})]
+#[derive(BaseStateTransition, Clone, Debug, Default, Builder, PartialEq)]
+pub struct {Name}StateTransition {}
+
+impl EventSourced<{Name}Event> for {Name}State {
+    fn apply(&self, event: &{Name}Event) -> Self {
+        let state = self.clone();
+        // TODO: update the state with the fields of each event
+        match event {
+{apply_arms}        }
+
+        state
+    }
+}
+
+/// Calls that transition the state of a `{Name}` contract.
+#[derive(Clone, Debug, PartialEq)]
+pub enum {Name}Input {
+{input_variants}}
+
+impl Default for {Name}Input {
+    fn default() -> Self {
+        {default_input}
+    }
+}
+
+#[derive(Configurable, ValidatorBase, Debug)]
+pub struct {Name}Validator {
+    contract: {Name}<Http, Wallet>,
+    state: {Name}State,
+    state_transition: {Name}StateTransition,
+}
+
+#[async_trait]
+impl Validator<{Name}State, {Name}StateTransition, {Name}Event> for {Name}Validator {
+    type Input = {Name}Input;
+
+    fn get_state(&self) -> {Name}State {
+        self.state.clone()
+    }
+
+    fn get_state_transition(&self) -> {Name}StateTransition {
+        self.state_transition.clone()
+    }
+
+    fn address(&self) -> Address {
+        self.contract.address()
+    }
+
+    fn abi(&self) -> &Abi {
+        self.contract.abi()
+    }
+
+    fn client(&self) -> &Client<Http, Wallet> {
+        self.contract.client()
+    }
+
+    fn transition_name(&self, input: &{Name}Input) -> &'static str {
+        match input {
+{name_arms}        }
+    }
+
+    async fn fetch_state(&self) -> Result<{Name}State, ContractError> {
+        {Name}State::fetch_state(&self.contract).await
+    }
+
+    async fn fetch_state_at(&self, block: U64) -> Result<{Name}State, ContractError> {
+        {Name}State::fetch_state_at(&self.contract, block).await
+    }
+
+    async fn query_events(
+        &self,
+        from: U64,
+        to: U64,
+    ) -> Result<Vec<(U64, {Name}Event)>, ContractError> {
+        // 1. Filter the contract's logs on the signatures of all of its events
+        let filter = Filter::new()
+            .address(self.contract.address())
+            .topic0(ValueOrArray::Array({Name}Event::signatures()));
+
+        // 2. Query event logs in chunks small enough for the node to accept
+        let client = self.contract.client();
+        let mut logs = query_range(from, to, DEFAULT_CHUNK_SIZE, |from, to| {
+            let filter = filter.clone().from_block(from).to_block(to);
+            async move { client.get_logs(&filter).await }
+        })
+        .await?;
+
+        // 3. Decode the logs in the order they were emitted, along with their block
+        sort_logs(&mut logs);
+        logs.into_iter()
+            .map(|log| {
+                let block = log.block_number.unwrap_or_default();
+                decode_event(self.contract.abi(), log).map(|event| (block, event))
+            })
+            .collect()
+    }
+
+    async fn sync_state(&mut self) -> Result<{Name}State, ContractError> {
+        // 1. Get the Validator's most recent state
+        let state = self.fetch_state().await?;
+
+        // 2. Update the Validator's state
+        self.state = state.clone();
+
+        Ok(state)
+    }
+
+    async fn sync_events(&mut self, block_number: U64) -> Result<Vec<{Name}Event>, ContractError> {
+        // 1. Query event logs for the specified block number
+        let events: Vec<{Name}Event> = self
+            .query_events(block_number, block_number)
+            .await?
+            .into_iter()
+            .map(|(_, event)| event)
+            .collect();
+
+        // 2. Update the Validator's state transition with the latest events
+        if !events.is_empty() {
+            self.state_transition.last_events = Some(events.clone());
+        }
+
+        Ok(events)
+    }
+
+    async fn sync_events_range(
+        &mut self,
+        from: U64,
+        to: U64,
+    ) -> Result<Vec<{Name}Event>, ContractError> {
+        // 1. Query event logs for the specified block range
+        let events: Vec<{Name}Event> = self
+            .query_events(from, to)
+            .await?
+            .into_iter()
+            .map(|(_, event)| event)
+            .collect();
+
+        // 2. Replace the Validator's event history, so that syncing overlapping ranges does
+        //    not record an event twice
+        self.state_transition.event_history = events.clone();
+
+        Ok(events)
+    }
+
+    async fn state_transition(
+        &mut self,
+        _initial_state: {Name}State,
+        input: {Name}Input,
+    ) -> Result<TransitionOutcome<{Name}State, {Name}Event>, ContractError> {
+        // 1. Broadcast a transaction to execute state transition and get its receipt, keeping
+        //    a revert as the transition's execution
+        let sent = match &input {
+{send_arms}        };
+        let result = match sent {
+            Ok(tx_hash) => self
+                .contract
+                .pending_transaction(tx_hash)
+                .await
+                .map_err(ContractError::from),
+            Err(err) => Err(err),
+        };
+        let execution = Execution::capture(result)?;
+
+        // 2. Build the state transition struct
+        let state_transition = {Name}StateTransitionBuilder::default()
+            .tx_receipt(execution.receipt().cloned().unwrap_or_default())
+            .event_history(self.state_transition.event_history.clone())
+            .build()
+            .unwrap();
+
+        // 3. Update the Validator with the most recent state transition
+        self.state_transition = state_transition;
+
+        // 4. Build the expected state and events based on inputs to the state transition
+        let expectation = match input {
+{expectation_arms}        };
+
+        Ok(TransitionOutcome {
+            expectation,
+            execution,
+        })
+    }
+}
+"#;
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_rust_type() {
+        assert_eq!(rust_type("address").unwrap(), "Address");
+        assert_eq!(rust_type("uint8").unwrap(), "u8");
+        assert_eq!(rust_type("uint256").unwrap(), "U256");
+        assert_eq!(rust_type("int24").unwrap(), "i32");
+        assert_eq!(rust_type("bytes32").unwrap(), "[u8; 32]");
+        assert_eq!(rust_type("string[]").unwrap(), "Vec<String>");
+        assert_eq!(rust_type("address[2][]").unwrap(), "Vec<[Address; 2]>");
+        assert!(rust_type("tuple").is_err());
+    }
+
+    #[test]
+    fn test_parse_abi() {
+        let json = fs::read_to_string("./contract-abi/SimpleStorage.json").unwrap();
+        let abi = parse_abi(&json).unwrap();
+
+        assert_eq!(abi.events, vec!["ValueChanged".to_string()]);
+        let state: Vec<&str> = abi
+            .state_functions()
+            .map(|function| function.name.as_str())
+            .collect();
+        assert_eq!(state, vec!["getValue", "lastSender"]);
+        let transitions: Vec<&AbiFunction> = abi.transition_functions().collect();
+        assert_eq!(
+            transitions,
+            vec![&AbiFunction {
+                name: "setValue".to_string(),
+                inputs: vec![AbiParam {
+                    name: "value".to_string(),
+                    rust_type: "String".to_string(),
+                }],
+                output: "()".to_string(),
+                view: false,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_validator_module() {
+        let json = fs::read_to_string("./contract-abi/SimpleStorage.json").unwrap();
+        let module = validator_module("SimpleStorage", &parse_abi(&json).unwrap()).unwrap();
+
+        assert!(module.contains("simple_storage::{SimpleStorage, ValueChangedFilter},"));
+        assert!(module.contains(
+            "pub struct SimpleStorageState {\n    #[view(get_value)]\n    value: String,\n    \
+             #[view(last_sender)]\n    last_sender: Address,\n}"
+        ));
+        assert!(module.contains("#[add_base_state_transition(ValueChangedFilter)]"));
+        assert!(module.contains("pub enum SimpleStorageInput {\n    SetValue(String),\n}"));
+        assert!(module.contains("#[hachiko(contract = \"SimpleStorage<Http, Wallet>\")]"));
+        assert!(module.contains("self.contract.set_value(value.clone()).send().await"));
+        assert!(module.contains("SimpleStorageInput::SetValue(..) => \"setValue\","));
+        assert!(module.contains(".address(self.contract.address())"));
+        assert!(!module.contains("{Name}") && !module.contains("{filters}"));
+    }
+
+    /// The module scaffolded for `SimpleStorage`, compiled with the tests so that a change to
+    /// the template which no longer builds fails here rather than in a new validator.
+    #[allow(dead_code)]
+    mod simple_storage_validator {
+        include!("scaffold/simple_storage_validator.rs");
+    }
+
+    #[test]
+    fn test_scaffolded_module() {
+        let json = fs::read_to_string("./contract-abi/SimpleStorage.json").unwrap();
+        let module = validator_module("SimpleStorage", &parse_abi(&json).unwrap()).unwrap();
+
+        assert!(
+            module == include_str!("scaffold/simple_storage_validator.rs"),
+            "the scaffolded module changed, update src/scaffold/simple_storage_validator.rs"
+        );
+    }
+
+    #[test]
+    fn test_validator_module_field_collisions() {
+        let json = fs::read_to_string("./contract-abi/SimpleStorage.json").unwrap();
+        let mut abi = parse_abi(&json).unwrap();
+        let view = |name: &str| AbiFunction {
+            name: name.to_string(),
+            inputs: vec![],
+            output: "U256".to_string(),
+            view: true,
+        };
+
+        abi.functions.push(view("value"));
+        assert_eq!(
+            validator_module("SimpleStorage", &abi).unwrap_err(),
+            "`getValue` and `value` would both be read into the state field `value`"
+        );
+
+        abi.functions.pop();
+        abi.functions.push(view("getLastBlockHash"));
+        assert_eq!(
+            validator_module("SimpleStorage", &abi).unwrap_err(),
+            "`getLastBlockHash` would be read into the state field `last_block_hash`, which holds \
+             the synced block"
+        );
+    }
+}
@@ -0,0 +1,232 @@
+use async_trait::async_trait;
+use config::{Config, File};
+use ethers::{
+    contract::ContractError,
+    core::{
+        abi::{Abi, Detokenize, InvalidOutputType, Token},
+        types::{Address, Filter, PrivateKey, TransactionReceipt, ValueOrArray, H256, U64},
+    },
+    providers::{Http, Provider},
+    signers::{Client, Wallet},
+};
+use validator_derive::{
+    add_base_state, add_base_state_transition, BaseState, BaseStateTransition, Configurable,
+    FetchState, ValidatorBase,
+};
+
+use std::{convert::TryFrom, str::FromStr, sync::Arc, time::Duration};
+
+use crate::{
+    events::{decode_event, query_range, sort_logs, DEFAULT_CHUNK_SIZE},
+    revert::Execution,
+    simple_storage::{SimpleStorage, ValueChangedFilter},
+    Configurable, ContractEvents, EventSourced, Expectation, FetchConfig, FieldDiff, HachikoError,
+    State, StateDiff, StateTransition, TransitionOutcome, Validator, ValidatorBase,
+    ValidatorConfig,
+};
+
+#[add_base_state]
+#[derive(BaseState, FetchState, Clone, Debug, Default, Builder)]
+#[hachiko(contract = "SimpleStorage<Http, Wallet>")]
+pub struct SimpleStorageState {
+    #[view(get_value)]
+    value: String,
+    #[view(last_sender)]
+    last_sender: Address,
+}
+
+#[add_base_state_transition(ValueChangedFilter)]
+#[derive(BaseStateTransition, Clone, Debug, Default, Builder, PartialEq)]
+pub struct SimpleStorageStateTransition {}
+
+impl EventSourced<SimpleStorageEvent> for SimpleStorageState {
+    fn apply(&self, event: &SimpleStorageEvent) -> Self {
+        let state = self.clone();
+        // TODO: update the state with the fields of each event
+        match event {
+            SimpleStorageEvent::ValueChanged(_event) => {}
+        }
+
+        state
+    }
+}
+
+/// Calls that transition the state of a `SimpleStorage` contract.
+#[derive(Clone, Debug, PartialEq)]
+pub enum SimpleStorageInput {
+    SetValue(String),
+}
+
+impl Default for SimpleStorageInput {
+    fn default() -> Self {
+        SimpleStorageInput::SetValue(Default::default())
+    }
+}
+
+#[derive(Configurable, ValidatorBase, Debug)]
+pub struct SimpleStorageValidator {
+    contract: SimpleStorage<Http, Wallet>,
+    state: SimpleStorageState,
+    state_transition: SimpleStorageStateTransition,
+}
+
+#[async_trait]
+impl Validator<SimpleStorageState, SimpleStorageStateTransition, SimpleStorageEvent> for SimpleStorageValidator {
+    type Input = SimpleStorageInput;
+
+    fn get_state(&self) -> SimpleStorageState {
+        self.state.clone()
+    }
+
+    fn get_state_transition(&self) -> SimpleStorageStateTransition {
+        self.state_transition.clone()
+    }
+
+    fn address(&self) -> Address {
+        self.contract.address()
+    }
+
+    fn abi(&self) -> &Abi {
+        self.contract.abi()
+    }
+
+    fn client(&self) -> &Client<Http, Wallet> {
+        self.contract.client()
+    }
+
+    fn transition_name(&self, input: &SimpleStorageInput) -> &'static str {
+        match input {
+            SimpleStorageInput::SetValue(..) => "setValue",
+        }
+    }
+
+    async fn fetch_state(&self) -> Result<SimpleStorageState, ContractError> {
+        SimpleStorageState::fetch_state(&self.contract).await
+    }
+
+    async fn fetch_state_at(&self, block: U64) -> Result<SimpleStorageState, ContractError> {
+        SimpleStorageState::fetch_state_at(&self.contract, block).await
+    }
+
+    async fn query_events(
+        &self,
+        from: U64,
+        to: U64,
+    ) -> Result<Vec<(U64, SimpleStorageEvent)>, ContractError> {
+        // 1. Filter the contract's logs on the signatures of all of its events
+        let filter = Filter::new()
+            .address(self.contract.address())
+            .topic0(ValueOrArray::Array(SimpleStorageEvent::signatures()));
+
+        // 2. Query event logs in chunks small enough for the node to accept
+        let client = self.contract.client();
+        let mut logs = query_range(from, to, DEFAULT_CHUNK_SIZE, |from, to| {
+            let filter = filter.clone().from_block(from).to_block(to);
+            async move { client.get_logs(&filter).await }
+        })
+        .await?;
+
+        // 3. Decode the logs in the order they were emitted, along with their block
+        sort_logs(&mut logs);
+        logs.into_iter()
+            .map(|log| {
+                let block = log.block_number.unwrap_or_default();
+                decode_event(self.contract.abi(), log).map(|event| (block, event))
+            })
+            .collect()
+    }
+
+    async fn sync_state(&mut self) -> Result<SimpleStorageState, ContractError> {
+        // 1. Get the Validator's most recent state
+        let state = self.fetch_state().await?;
+
+        // 2. Update the Validator's state
+        self.state = state.clone();
+
+        Ok(state)
+    }
+
+    async fn sync_events(&mut self, block_number: U64) -> Result<Vec<SimpleStorageEvent>, ContractError> {
+        // 1. Query event logs for the specified block number
+        let events: Vec<SimpleStorageEvent> = self
+            .query_events(block_number, block_number)
+            .await?
+            .into_iter()
+            .map(|(_, event)| event)
+            .collect();
+
+        // 2. Update the Validator's state transition with the latest events
+        if !events.is_empty() {
+            self.state_transition.last_events = Some(events.clone());
+        }
+
+        Ok(events)
+    }
+
+    async fn sync_events_range(
+        &mut self,
+        from: U64,
+        to: U64,
+    ) -> Result<Vec<SimpleStorageEvent>, ContractError> {
+        // 1. Query event logs for the specified block range
+        let events: Vec<SimpleStorageEvent> = self
+            .query_events(from, to)
+            .await?
+            .into_iter()
+            .map(|(_, event)| event)
+            .collect();
+
+        // 2. Replace the Validator's event history, so that syncing overlapping ranges does
+        //    not record an event twice
+        self.state_transition.event_history = events.clone();
+
+        Ok(events)
+    }
+
+    async fn state_transition(
+        &mut self,
+        _initial_state: SimpleStorageState,
+        input: SimpleStorageInput,
+    ) -> Result<TransitionOutcome<SimpleStorageState, SimpleStorageEvent>, ContractError> {
+        // 1. Broadcast a transaction to execute state transition and get its receipt, keeping
+        //    a revert as the transition's execution
+        let sent = match &input {
+            SimpleStorageInput::SetValue(value) => {
+                self.contract.set_value(value.clone()).send().await
+            }
+        };
+        let result = match sent {
+            Ok(tx_hash) => self
+                .contract
+                .pending_transaction(tx_hash)
+                .await
+                .map_err(ContractError::from),
+            Err(err) => Err(err),
+        };
+        let execution = Execution::capture(result)?;
+
+        // 2. Build the state transition struct
+        let state_transition = SimpleStorageStateTransitionBuilder::default()
+            .tx_receipt(execution.receipt().cloned().unwrap_or_default())
+            .event_history(self.state_transition.event_history.clone())
+            .build()
+            .unwrap();
+
+        // 3. Update the Validator with the most recent state transition
+        self.state_transition = state_transition;
+
+        // 4. Build the expected state and events based on inputs to the state transition
+        let expectation = match input {
+            // TODO: build the state and events expected after `setValue`
+            SimpleStorageInput::SetValue(..) => Expectation::Success {
+                state: self.state.clone(),
+                events: vec![],
+            },
+        };
+
+        Ok(TransitionOutcome {
+            expectation,
+            execution,
+        })
+    }
+}
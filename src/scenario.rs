@@ -0,0 +1,158 @@
+use std::fmt;
+
+use crate::{
+    validate::{validate, ValidationReport},
+    ContractEvents, State, StateTransition, ValidationError, Validator,
+};
+
+/// A single transition of a scenario, sent by one of the scenario's actors.
+#[derive(Clone, Debug)]
+pub struct Step<I> {
+    /// Index of the validator, and hence of the wallet, that sends the transition
+    pub actor: usize,
+    pub input: I,
+}
+
+/// A sequence of transitions, each validated against the state left by the previous one.
+#[derive(Clone, Debug)]
+pub struct Scenario<I> {
+    pub name: String,
+    pub steps: Vec<Step<I>>,
+}
+
+impl<I> Scenario<I> {
+    pub fn new(name: &str) -> Self {
+        Scenario {
+            name: name.to_string(),
+            steps: vec![],
+        }
+    }
+
+    /// Appends a transition sent by `actor`.
+    pub fn step(mut self, actor: usize, input: I) -> Self {
+        self.steps.push(Step { actor, input });
+        self
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct StepReport {
+    pub index: usize,
+    pub actor: usize,
+    pub report: ValidationReport,
+}
+
+/// The history of a scenario up to, and including, its first diverging step.
+#[derive(Clone, Debug)]
+pub struct ScenarioReport {
+    pub name: String,
+    pub steps: Vec<StepReport>,
+    pub first_failure: Option<usize>,
+}
+
+impl ScenarioReport {
+    pub fn passed(&self) -> bool {
+        self.first_failure.is_none()
+    }
+}
+
+impl fmt::Display for ScenarioReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.first_failure {
+            None => writeln!(f, "scenario {} passed", self.name)?,
+            Some(index) => writeln!(f, "scenario {} diverged at step {}", self.name, index)?,
+        }
+
+        for step in self.steps.iter() {
+            write!(
+                f,
+                "[step {}, actor {}] {}",
+                step.index, step.actor, step.report
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Runs every step of `scenario` with the validator of its actor.
+///
+/// All `actors` validate the same contract, each with its own wallet. The scenario stops at
+/// the first step whose state, events or invariants diverge from the expected ones.
+pub async fn run_scenario<S, T, E, V>(
+    actors: &mut [V],
+    scenario: &Scenario<V::Input>,
+) -> Result<ScenarioReport, ValidationError>
+where
+    S: State,
+    T: StateTransition,
+    E: ContractEvents + std::fmt::Debug + PartialEq,
+    V: Validator<S, T, E>,
+{
+    if let Some(step) = scenario
+        .steps
+        .iter()
+        .find(|step| step.actor >= actors.len())
+    {
+        return Err(ValidationError::InvalidScenario(format!(
+            "step sent by actor {} but only {} actors were given",
+            step.actor,
+            actors.len()
+        )));
+    }
+
+    let mut steps = vec![];
+    let mut first_failure = None;
+
+    for (index, step) in scenario.steps.iter().enumerate() {
+        let report = validate(&mut actors[step.actor], step.input.clone()).await?;
+        let passed = report.passed();
+
+        steps.push(StepReport {
+            index,
+            actor: step.actor,
+            report,
+        });
+
+        if !passed {
+            first_failure = Some(index);
+            break;
+        }
+    }
+
+    Ok(ScenarioReport {
+        name: scenario.name.clone(),
+        steps,
+        first_failure,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::{
+        fixture::SimpleStorageChain,
+        simple_storage_validator::{SimpleStorageInput, SimpleStorageValidator},
+    };
+
+    #[tokio::test]
+    async fn test_scenario_deploy() {
+        // 1. deploy the contract with the first account
+        let chain = SimpleStorageChain::deploy().await;
+
+        // 2. create one validator per account
+        let mut actors: Vec<SimpleStorageValidator> =
+            (0..2).map(|index| chain.validator(index)).collect();
+
+        // 3. A sets, B sets, A sets
+        let scenario = Scenario::new("alternating senders")
+            .step(0, SimpleStorageInput::SetValue("a".to_string()))
+            .step(1, SimpleStorageInput::SetValue("b".to_string()))
+            .step(0, SimpleStorageInput::SetValue("c".to_string()));
+
+        let report = run_scenario(&mut actors, &scenario).await.unwrap();
+        assert!(report.passed(), "{}", report);
+        assert_eq!(report.steps.len(), 3);
+    }
+}
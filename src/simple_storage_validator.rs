@@ -2,26 +2,38 @@ use async_trait::async_trait;
 use config::{Config, File};
 use ethers::{
     contract::ContractError,
-    core::types::{Address, PrivateKey, TransactionReceipt, U64},
+    core::{
+        abi::{Abi, Detokenize, InvalidOutputType, Token},
+        types::{Address, Filter, PrivateKey, TransactionReceipt, ValueOrArray, H256, U256, U64},
+    },
     providers::{Http, Provider},
-    signers::Wallet,
+    signers::{Client, Wallet},
 };
 use validator_derive::{
     add_base_state, add_base_state_transition, BaseState, BaseStateTransition, Configurable,
-    ValidatorBase,
+    FetchState, ValidatorBase,
 };
 
+use rand::{distributions::Alphanumeric, Rng};
+
 use std::{convert::TryFrom, str::FromStr, sync::Arc, time::Duration};
 
 use crate::{
+    events::{decode_event, query_range, sort_logs, DEFAULT_CHUNK_SIZE},
+    revert::Execution,
     simple_storage::{SimpleStorage, ValueChangedFilter},
-    Configurable, FetchConfig, State, StateTransition, Validator, ValidatorBase, ValidatorConfig,
+    Configurable, ContractEvents, EventSourced, Expectation, FetchConfig, FieldDiff, Fuzzable,
+    HachikoError, Invariant, State, StateDiff, StateTransition, TransitionOutcome, Validator,
+    ValidatorBase, ValidatorConfig,
 };
 
 #[add_base_state]
-#[derive(BaseState, Clone, Debug, Default, Builder)]
+#[derive(BaseState, FetchState, Clone, Debug, Default, Builder)]
+#[hachiko(contract = "SimpleStorage<Http, Wallet>")]
 pub struct SimpleStorageState {
+    #[view(get_value)]
     value: String,
+    #[view(last_sender)]
     last_sender: Address,
 }
 
@@ -29,6 +41,140 @@ pub struct SimpleStorageState {
 #[derive(BaseStateTransition, Clone, Debug, Default, Builder, PartialEq)]
 pub struct SimpleStorageStateTransition {}
 
+impl EventSourced<SimpleStorageEvent> for SimpleStorageState {
+    fn apply(&self, event: &SimpleStorageEvent) -> Self {
+        let mut state = self.clone();
+        match event {
+            // Only applies to `setValue`, the constructor emits `ValueChanged` without setting
+            // `lastSender`
+            SimpleStorageEvent::ValueChanged(event) => {
+                state.value = event.new_value.clone();
+                state.last_sender = event.author;
+            }
+        }
+
+        state
+    }
+}
+
+/// Gas budget of a `setValue` call when both the old and the new value fit in a single word.
+const SET_VALUE_GAS: u64 = 100_000;
+
+/// Additional gas budget of a `setValue` call for every further word of the longer value.
+const SET_VALUE_GAS_PER_WORD: u64 = 25_000;
+
+/// Gas budget of a `setValue` call replacing a value of `old_len` bytes with one of `new_len`
+/// bytes. The call reads the old value for its event and clears the words the new value no
+/// longer uses, so the budget follows the longer of the two.
+fn set_value_gas_budget(old_len: usize, new_len: usize) -> U256 {
+    let words = old_len.max(new_len).div_ceil(32) as u64;
+
+    U256::from(SET_VALUE_GAS + SET_VALUE_GAS_PER_WORD * words.saturating_sub(1))
+}
+
+/// Calls that transition the state of a `SimpleStorage` contract.
+#[derive(Clone, Debug, PartialEq)]
+pub enum SimpleStorageInput {
+    SetValue(String),
+}
+
+impl Default for SimpleStorageInput {
+    fn default() -> Self {
+        SimpleStorageInput::SetValue("hi".to_string())
+    }
+}
+
+impl Fuzzable for SimpleStorageInput {
+    fn generate<R: Rng>(rng: &mut R) -> Self {
+        // Favour the edge values: empty strings, non-ascii and strings spanning many words
+        let value = match rng.gen_range(0, 4) {
+            0 => String::new(),
+            1 => "\u{0}\u{1f980}".repeat(rng.gen_range(1, 16)),
+            2 => {
+                let len = rng.gen_range(32, 2048);
+                (0..len).map(|_| rng.sample(Alphanumeric)).collect()
+            }
+            _ => {
+                let len = rng.gen_range(1, 32);
+                (0..len).map(|_| rng.sample(Alphanumeric)).collect()
+            }
+        };
+
+        SimpleStorageInput::SetValue(value)
+    }
+
+    fn shrink(&self) -> Vec<Self> {
+        match self {
+            SimpleStorageInput::SetValue(value) if value.is_empty() => vec![],
+            SimpleStorageInput::SetValue(value) => {
+                let len = value.chars().count();
+                let mut candidates = vec![String::new()];
+                if len > 2 {
+                    candidates.push(value.chars().take(len / 2).collect());
+                }
+                if len > 1 {
+                    candidates.push(value.chars().take(len - 1).collect());
+                }
+
+                candidates
+                    .into_iter()
+                    .map(SimpleStorageInput::SetValue)
+                    .collect()
+            }
+        }
+    }
+}
+
+/// Once `setValue` has been called, `lastSender` never returns to the zero address.
+pub struct LastSenderNeverReset;
+
+impl Invariant<SimpleStorageState> for LastSenderNeverReset {
+    fn name(&self) -> &'static str {
+        "last_sender_never_reset"
+    }
+
+    fn check(
+        &self,
+        previous: Option<&SimpleStorageState>,
+        state: &SimpleStorageState,
+    ) -> Result<(), String> {
+        match previous {
+            Some(previous) if !previous.last_sender.is_zero() && state.last_sender.is_zero() => {
+                Err(format!(
+                    "last_sender reset from {:?} to the zero address",
+                    previous.last_sender
+                ))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// A change of value is always recorded along with a non-zero `lastSender`.
+pub struct ValueChangeHasSender;
+
+impl Invariant<SimpleStorageState> for ValueChangeHasSender {
+    fn name(&self) -> &'static str {
+        "value_change_has_sender"
+    }
+
+    fn check(
+        &self,
+        previous: Option<&SimpleStorageState>,
+        state: &SimpleStorageState,
+    ) -> Result<(), String> {
+        match previous {
+            Some(previous) if previous.value != state.value && state.last_sender.is_zero() => {
+                Err(format!(
+                    "value changed from {:?} to {:?} without a last_sender",
+                    previous.value, state.value
+                ))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
 #[derive(Configurable, ValidatorBase, Debug)]
 pub struct SimpleStorageValidator {
     contract: SimpleStorage<Http, Wallet>,
@@ -37,9 +183,11 @@ pub struct SimpleStorageValidator {
 }
 
 #[async_trait]
-impl Validator<SimpleStorageState, SimpleStorageStateTransition, ValueChangedFilter>
+impl Validator<SimpleStorageState, SimpleStorageStateTransition, SimpleStorageEvent>
     for SimpleStorageValidator
 {
+    type Input = SimpleStorageInput;
+
     fn get_state(&self) -> SimpleStorageState {
         self.state.clone()
     }
@@ -48,21 +196,73 @@ impl Validator<SimpleStorageState, SimpleStorageStateTransition, ValueChangedFil
         self.state_transition.clone()
     }
 
+    fn address(&self) -> Address {
+        self.contract.address()
+    }
+
+    fn abi(&self) -> &Abi {
+        self.contract.abi()
+    }
+
+    fn client(&self) -> &Client<Http, Wallet> {
+        self.contract.client()
+    }
+
+    fn transition_name(&self, input: &SimpleStorageInput) -> &'static str {
+        match input {
+            SimpleStorageInput::SetValue(_) => "setValue",
+        }
+    }
+
+    fn gas_budget(&self, input: &SimpleStorageInput) -> Option<U256> {
+        match input {
+            SimpleStorageInput::SetValue(value) => {
+                Some(set_value_gas_budget(self.state.value.len(), value.len()))
+            }
+        }
+    }
+
+    fn invariants(&self) -> Vec<Box<dyn Invariant<SimpleStorageState>>> {
+        vec![
+            Box::new(LastSenderNeverReset),
+            Box::new(ValueChangeHasSender),
+        ]
+    }
+
     async fn fetch_state(&self) -> Result<SimpleStorageState, ContractError> {
-        // 1. Fetch the most recent state from the blockchain
-        let value = self.contract.get_value().call().await?;
-        let last_sender = self.contract.last_sender().call().await?;
-        let last_block = self.contract.client().get_block_number().await?;
-
-        // 2. Build the state with the above values
-        let state = SimpleStorageStateBuilder::default()
-            .value(value)
-            .last_sender(last_sender)
-            .last_block(Some(last_block))
-            .build()
-            .unwrap();
+        SimpleStorageState::fetch_state(&self.contract).await
+    }
 
-        Ok(state)
+    async fn fetch_state_at(&self, block: U64) -> Result<SimpleStorageState, ContractError> {
+        SimpleStorageState::fetch_state_at(&self.contract, block).await
+    }
+
+    async fn query_events(
+        &self,
+        from: U64,
+        to: U64,
+    ) -> Result<Vec<(U64, SimpleStorageEvent)>, ContractError> {
+        // 1. Filter the contract's logs on the signatures of all of its events
+        let filter = Filter::new()
+            .address(self.contract.address())
+            .topic0(ValueOrArray::Array(SimpleStorageEvent::signatures()));
+
+        // 2. Query event logs in chunks small enough for the node to accept
+        let client = self.contract.client();
+        let mut logs = query_range(from, to, DEFAULT_CHUNK_SIZE, |from, to| {
+            let filter = filter.clone().from_block(from).to_block(to);
+            async move { client.get_logs(&filter).await }
+        })
+        .await?;
+
+        // 3. Decode the logs in the order they were emitted, along with their block
+        sort_logs(&mut logs);
+        logs.into_iter()
+            .map(|log| {
+                let block = log.block_number.unwrap_or_default();
+                decode_event(self.contract.abi(), log).map(|event| (block, event))
+            })
+            .collect()
     }
 
     async fn sync_state(&mut self) -> Result<SimpleStorageState, ContractError> {
@@ -78,60 +278,105 @@ impl Validator<SimpleStorageState, SimpleStorageStateTransition, ValueChangedFil
     async fn sync_events(
         &mut self,
         block_number: U64,
-    ) -> Result<Vec<ValueChangedFilter>, ContractError> {
+    ) -> Result<Vec<SimpleStorageEvent>, ContractError> {
         // 1. Query event logs for the specified block number
-        let logs = self
-            .contract
-            .value_changed_filter()
-            .from_block(block_number)
-            .to_block(block_number)
-            .query()
-            .await?;
+        let events: Vec<SimpleStorageEvent> = self
+            .query_events(block_number, block_number)
+            .await?
+            .into_iter()
+            .map(|(_, event)| event)
+            .collect();
 
         // 2. Update the Validator's state transition with the latest events
-        if logs.len() > 0 {
-            self.state_transition.last_events = Some(logs.clone());
+        if !events.is_empty() {
+            self.state_transition.last_events = Some(events.clone());
         }
 
-        Ok(logs)
+        Ok(events)
+    }
+
+    async fn sync_events_range(
+        &mut self,
+        from: U64,
+        to: U64,
+    ) -> Result<Vec<SimpleStorageEvent>, ContractError> {
+        // 1. Query event logs for the specified block range
+        let events: Vec<SimpleStorageEvent> = self
+            .query_events(from, to)
+            .await?
+            .into_iter()
+            .map(|(_, event)| event)
+            .collect();
+
+        // 2. Replace the Validator's event history, so that syncing overlapping ranges does
+        //    not record an event twice
+        self.state_transition.event_history = events.clone();
+
+        Ok(events)
     }
 
     async fn state_transition(
         &mut self,
         _initial_state: SimpleStorageState,
-    ) -> Result<(SimpleStorageState, Vec<ValueChangedFilter>), ContractError> {
-        // 1. Broadcast a transaction to execute state transition
-        let tx_hash = self.contract.set_value("hi".to_owned()).send().await?;
-
-        // 2. Get receipt for the transaction
-        let tx_receipt = self.contract.pending_transaction(tx_hash).await?;
-        let block_number = tx_receipt.block_number;
+        input: SimpleStorageInput,
+    ) -> Result<TransitionOutcome<SimpleStorageState, SimpleStorageEvent>, ContractError> {
+        // 1. Broadcast a transaction to execute state transition and get its receipt, keeping
+        //    a revert as the transition's execution
+        let call = match &input {
+            SimpleStorageInput::SetValue(value) => self.contract.set_value(value.clone()),
+        };
+        let result = match call.send().await {
+            Ok(tx_hash) => self
+                .contract
+                .pending_transaction(tx_hash)
+                .await
+                .map_err(ContractError::from),
+            Err(err) => Err(err),
+        };
+        let execution = Execution::capture(result)?;
+        let block_number = execution
+            .receipt()
+            .and_then(|tx_receipt| tx_receipt.block_number);
 
-        // 3. Build the state transition struct
+        // 2. Build the state transition struct
         let state_transition = SimpleStorageStateTransitionBuilder::default()
-            .tx_receipt(tx_receipt)
+            .tx_receipt(execution.receipt().cloned().unwrap_or_default())
+            .event_history(self.state_transition.event_history.clone())
             .build()
             .unwrap();
 
-        // 4. Update the Validator with the most recent state transition
+        // 3. Update the Validator with the most recent state transition
         self.state_transition = state_transition;
 
-        // 5. Build the expected state based on inputs to the state transition
-        let expected_state = SimpleStorageStateBuilder::default()
-            .value("hi".to_string())
-            .last_sender(self.contract.client().address())
-            .last_block(block_number)
-            .build()
-            .unwrap();
+        // 4. Build the expected state and events based on inputs to the state transition
+        let sender = self.contract.client().address();
+        let expectation = match input {
+            SimpleStorageInput::SetValue(value) => {
+                let expected_state = SimpleStorageStateBuilder::default()
+                    .value(value.clone())
+                    .last_sender(sender)
+                    .last_block(block_number)
+                    .build()
+                    .unwrap();
 
-        let expected_events = vec![ValueChangedFilter {
-            author: self.contract.client().address(),
-            old_author: self.state.last_sender.clone(),
-            old_value: self.state.value.clone(),
-            new_value: "hi".to_string(),
-        }];
+                let expected_events = vec![SimpleStorageEvent::ValueChanged(ValueChangedFilter {
+                    author: sender,
+                    old_author: self.state.last_sender.clone(),
+                    old_value: self.state.value.clone(),
+                    new_value: value,
+                })];
 
-        Ok((expected_state, expected_events))
+                Expectation::Success {
+                    state: expected_state,
+                    events: expected_events,
+                }
+            }
+        };
+
+        Ok(TransitionOutcome {
+            expectation,
+            execution,
+        })
     }
 }
 
@@ -139,8 +384,214 @@ impl Validator<SimpleStorageState, SimpleStorageStateTransition, ValueChangedFil
 mod test {
     use super::*;
 
+    use crate::{fixture::SimpleStorageChain, validate::validate, ValidationError};
+
+    use std::{error::Error, fs};
+
+    const DEFAULT_CONFIG: &str = include_str!("../config/default/simple_storage_validator.toml");
+
+    /// Writes `contents` as the validator's configuration file, in a directory of its own.
+    fn config_dir(name: &str, contents: &str) -> String {
+        let dir = std::env::temp_dir().join(format!("hachiko-{}-{}", name, std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("simple_storage_validator.toml"), contents).unwrap();
+
+        dir.to_str().unwrap().to_string()
+    }
+
     #[test]
     fn test_init() {
-        let _validator = SimpleStorageValidator::init();
+        let _validator = SimpleStorageValidator::init().unwrap();
+    }
+
+    /// A generic validator whose contract binding is not named `contract`.
+    #[derive(Configurable, ValidatorBase)]
+    struct MarkedValidator<T: Default> {
+        #[hachiko(contract)]
+        storage: SimpleStorage<Http, Wallet>,
+        extra: T,
+    }
+
+    #[test]
+    fn test_init_marked_contract_field() {
+        let config = SimpleStorageValidator::fetch_config().unwrap();
+        let address = config.address;
+
+        let validator = MarkedValidator::<u8>::init_with(config).unwrap();
+        assert_eq!(validator.storage.address(), address);
+        assert_eq!(validator.extra, 0);
+    }
+
+    #[test]
+    fn test_fetch_config_errors() {
+        assert!(matches!(
+            SimpleStorageValidator::fetch_config_from("missing/dir"),
+            Err(HachikoError::MissingConfig { .. })
+        ));
+
+        let dir = config_dir("malformed", "private_key = \"1529c38c");
+        assert!(matches!(
+            SimpleStorageValidator::fetch_config_from(&dir),
+            Err(HachikoError::InvalidConfig { .. })
+        ));
+
+        let dir = config_dir("incomplete", "url = \"http://localhost:8545\"");
+        assert!(matches!(
+            SimpleStorageValidator::fetch_config_from(&dir),
+            Err(HachikoError::InvalidConfig { .. })
+        ));
+
+        let contents = DEFAULT_CONFIG.replace(
+            "1529c38c9c5284e9378afa5dafaf802decd9738e6685d4ed4c889e5c2b9b0086",
+            "not a key",
+        );
+        let dir = config_dir("private-key", &contents);
+        let err = match SimpleStorageValidator::fetch_config_from(&dir) {
+            Err(err @ HachikoError::InvalidPrivateKey { .. }) => err,
+            _ => panic!("expected an invalid private key"),
+        };
+        // The key error stays reachable once wrapped as a validation error
+        let err = ValidationError::from(err);
+        assert!(err.source().and_then(|err| err.source()).is_some());
+
+        let contents =
+            DEFAULT_CONFIG.replace("d9F9304329451Dd31908BC61C0F87e2AA90aacD6", "not an address");
+        let dir = config_dir("address", &contents);
+        match SimpleStorageValidator::fetch_config_from(&dir) {
+            Err(HachikoError::InvalidAddress { address, .. }) => {
+                assert_eq!(address, "not an address")
+            }
+            _ => panic!("expected an invalid address"),
+        }
+    }
+
+    #[test]
+    fn test_init_with_invalid_url() {
+        let mut config = SimpleStorageValidator::fetch_config().unwrap();
+        config.url = "not a url".to_string();
+
+        match SimpleStorageValidator::init_with(config) {
+            Err(HachikoError::InvalidUrl { url, .. }) => assert_eq!(url, "not a url"),
+            _ => panic!("expected an invalid url"),
+        }
+    }
+
+    #[test]
+    fn test_state_diff() {
+        let expected = SimpleStorageStateBuilder::default()
+            .value("hi".to_string())
+            .last_sender(Address::zero())
+            .last_block(Some(U64::from(2)))
+            .build()
+            .unwrap();
+        let actual = SimpleStorageStateBuilder::default()
+            .value("initial value".to_string())
+            .last_sender(Address::zero())
+            .last_block(Some(U64::from(3)))
+            .build()
+            .unwrap();
+
+        let diffs = expected.diff(&actual);
+        assert_eq!(diffs.len(), 1);
+        assert_eq!(
+            diffs[0].to_string(),
+            "value: expected \"hi\", got \"initial value\""
+        );
+        assert!(expected.diff(&expected).is_empty());
+    }
+
+    #[test]
+    fn test_shrink_input() {
+        let input = SimpleStorageInput::SetValue("abcd".to_string());
+        assert_eq!(
+            input.shrink(),
+            vec![
+                SimpleStorageInput::SetValue("".to_string()),
+                SimpleStorageInput::SetValue("ab".to_string()),
+                SimpleStorageInput::SetValue("abc".to_string()),
+            ]
+        );
+        assert!(SimpleStorageInput::SetValue("".to_string())
+            .shrink()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_set_value_gas_budget() {
+        assert_eq!(set_value_gas_budget(0, 0), U256::from(100_000));
+        assert_eq!(set_value_gas_budget(5, 32), U256::from(100_000));
+        assert_eq!(set_value_gas_budget(5, 33), U256::from(125_000));
+        assert_eq!(set_value_gas_budget(64, 0), U256::from(125_000));
+
+        // A short value replacing a long one pays for the words it clears
+        assert_eq!(set_value_gas_budget(2048, 1), U256::from(1_675_000));
+        assert_eq!(set_value_gas_budget(2048, 1), set_value_gas_budget(1, 2048));
+    }
+
+    #[test]
+    fn test_invariants() {
+        let sender = Address::from_low_u64_be(1);
+        let set = SimpleStorageStateBuilder::default()
+            .value("hi".to_string())
+            .last_sender(sender)
+            .build()
+            .unwrap();
+        let reset = SimpleStorageStateBuilder::default()
+            .value("bye".to_string())
+            .last_sender(Address::zero())
+            .build()
+            .unwrap();
+
+        assert!(LastSenderNeverReset.check(None, &reset).is_ok());
+        assert!(LastSenderNeverReset.check(Some(&set), &set).is_ok());
+        assert!(LastSenderNeverReset.check(Some(&set), &reset).is_err());
+
+        assert!(ValueChangeHasSender.check(Some(&reset), &set).is_ok());
+        assert!(ValueChangeHasSender.check(Some(&set), &reset).is_err());
+    }
+
+    #[test]
+    fn test_apply_event() {
+        let author = Address::from_low_u64_be(1);
+        let state = SimpleStorageState::default().apply(&SimpleStorageEvent::ValueChanged(
+            ValueChangedFilter {
+                author,
+                old_author: Address::zero(),
+                old_value: "".to_string(),
+                new_value: "hi".to_string(),
+            },
+        ));
+
+        assert_eq!(state.value, "hi");
+        assert_eq!(state.last_sender, author);
+    }
+
+    #[tokio::test]
+    async fn test_sync_overlapping_event_ranges() {
+        let mut validator = SimpleStorageChain::deploy().await.validator(0);
+        let deploy_block = validator.client().get_block_number().await.unwrap();
+        for value in ["hi", "bye"].iter() {
+            let input = SimpleStorageInput::SetValue(value.to_string());
+            validate(&mut validator, input).await.unwrap();
+        }
+
+        // 1. Every event is returned along with the block that emitted it
+        let events = validator
+            .query_events(deploy_block + 1, deploy_block + 2)
+            .await
+            .unwrap();
+        let blocks: Vec<U64> = events.iter().map(|(block, _)| *block).collect();
+        assert_eq!(blocks, vec![deploy_block + 1, deploy_block + 2]);
+
+        // 2. Syncing overlapping ranges records each event once
+        validator
+            .sync_events_range(deploy_block + 1, deploy_block + 2)
+            .await
+            .unwrap();
+        validator
+            .sync_events_range(deploy_block + 1, deploy_block + 2)
+            .await
+            .unwrap();
+        assert_eq!(validator.state_transition.event_history.len(), 2);
     }
 }
@@ -1,35 +1,799 @@
-use ethers::contract::ContractError;
+use ethers::core::{
+    abi::Abi,
+    types::{Address, TransactionReceipt, H256, U256, U64},
+};
+use rand::{rngs::StdRng, Rng, SeedableRng};
 
-use crate::{State, StateTransition, Validator};
+use std::{
+    fmt,
+    time::{Duration, Instant},
+};
 
-#[allow(dead_code)]
-pub async fn validate<S, T, E, V>(validator: &mut V) -> Result<(), ContractError>
+use crate::{
+    error::ValidationError, events::decode_event, fixture::ChainFixture, revert::Execution,
+    ContractEvents, EventSourced, Expectation, FieldDiff, Fuzzable, State, StateDiff,
+    StateTransition, TransitionOutcome, Validator,
+};
+
+/// Time spent in a single step of a validation.
+#[derive(Clone, Debug)]
+pub struct StepTiming {
+    pub step: &'static str,
+    pub duration: Duration,
+}
+
+/// A check that did not hold during a validation.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Failure {
+    /// The synced state differs from the state expected after the transition
+    StateMismatch(Vec<FieldDiff>),
+    /// The emitted events differ from the events expected from the transition
+    EventsMismatch,
+    /// A synced state broke one of the validator's invariants
+    InvariantViolation {
+        invariant: &'static str,
+        block: Option<U64>,
+        reason: String,
+    },
+    /// The transaction reverted although it was expected to succeed
+    UnexpectedRevert { reason: Option<String> },
+    /// The transaction succeeded although it was expected to revert
+    MissingRevert { expected_reason: Option<String> },
+    /// The transaction reverted, but not with the expected reason
+    RevertReasonMismatch {
+        expected: String,
+        actual: Option<String>,
+    },
+    /// The receipt of a mined transaction does not report success
+    ReceiptStatus { status: Option<U64> },
+    /// The transaction was not sent to the validator's contract
+    ReceiptRecipient {
+        expected: Address,
+        actual: Option<Address>,
+    },
+    /// The receipt holds a different number of logs than the expected events
+    LogCount { expected: usize, actual: usize },
+    /// The first topic of a log is not the signature of the expected event
+    LogTopic {
+        index: usize,
+        expected: H256,
+        actual: Option<H256>,
+    },
+    /// A log does not hold the expected event
+    LogEvent {
+        index: usize,
+        expected: String,
+        actual: String,
+    },
+    /// A log could not be decoded into one of the contract's events
+    LogDecode { index: usize, reason: String },
+    /// A log was emitted by another contract than the validator's
+    LogAddress {
+        index: usize,
+        expected: Address,
+        actual: Address,
+    },
+    /// The transaction used more gas than the transition's budget
+    GasBudgetExceeded { budget: U256, gas_used: U256 },
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Failure::StateMismatch(_) => write!(f, "state does not match the expected state"),
+            Failure::EventsMismatch => write!(f, "events do not match the expected events"),
+            Failure::InvariantViolation {
+                invariant,
+                block: Some(block),
+                reason,
+            } => write!(
+                f,
+                "invariant {} broken at block {}: {}",
+                invariant, block, reason
+            ),
+            Failure::InvariantViolation {
+                invariant, reason, ..
+            } => write!(f, "invariant {} broken: {}", invariant, reason),
+            Failure::UnexpectedRevert {
+                reason: Some(reason),
+            } => {
+                write!(f, "transaction reverted: {}", reason)
+            }
+            Failure::UnexpectedRevert { reason: None } => write!(f, "transaction reverted"),
+            Failure::MissingRevert {
+                expected_reason: Some(reason),
+            } => write!(f, "transaction did not revert with {:?}", reason),
+            Failure::MissingRevert {
+                expected_reason: None,
+            } => write!(f, "transaction did not revert"),
+            Failure::RevertReasonMismatch {
+                expected,
+                actual: Some(actual),
+            } => write!(
+                f,
+                "transaction reverted with {:?}, expected {:?}",
+                actual, expected
+            ),
+            Failure::RevertReasonMismatch { expected, .. } => write!(
+                f,
+                "transaction reverted without a reason, expected {:?}",
+                expected
+            ),
+            Failure::ReceiptStatus {
+                status: Some(status),
+            } => {
+                write!(f, "receipt has status {}, expected 1", status)
+            }
+            Failure::ReceiptStatus { status: None } => write!(f, "receipt has no status"),
+            Failure::ReceiptRecipient {
+                expected,
+                actual: Some(actual),
+            } => write!(
+                f,
+                "transaction sent to {:?}, expected {:?}",
+                actual, expected
+            ),
+            Failure::ReceiptRecipient { expected, .. } => write!(
+                f,
+                "transaction created a contract, expected a call to {:?}",
+                expected
+            ),
+            Failure::LogCount { expected, actual } => {
+                write!(f, "receipt has {} logs, expected {}", actual, expected)
+            }
+            Failure::LogTopic {
+                index,
+                expected,
+                actual: Some(actual),
+            } => write!(
+                f,
+                "log {} has signature {:?}, expected {:?}",
+                index, actual, expected
+            ),
+            Failure::LogTopic {
+                index, expected, ..
+            } => write!(
+                f,
+                "log {} has no topics, expected signature {:?}",
+                index, expected
+            ),
+            Failure::LogEvent {
+                index,
+                expected,
+                actual,
+            } => write!(f, "log {} holds {}, expected {}", index, actual, expected),
+            Failure::LogDecode { index, reason } => {
+                write!(f, "log {} could not be decoded: {}", index, reason)
+            }
+            Failure::LogAddress {
+                index,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "log {} emitted by {:?}, expected {:?}",
+                index, actual, expected
+            ),
+            Failure::GasBudgetExceeded { budget, gas_used } => {
+                write!(f, "transaction used {} gas, budget is {}", gas_used, budget)
+            }
+        }
+    }
+}
+
+/// The result of a single `validate` run.
+///
+/// States and events are kept in their `Debug` form, so that reports from
+/// validators over different contracts can be collected together.
+#[derive(Clone, Debug)]
+pub struct ValidationReport {
+    /// Name of the transition, as given by `Validator::transition_name`
+    pub transition: &'static str,
+    pub input: String,
+    pub expected_state: String,
+    pub actual_state: String,
+    pub expected_events: Vec<String>,
+    pub actual_events: Vec<String>,
+    pub block_number: Option<U64>,
+    /// Hash of the transaction, unless it was rejected before being mined
+    pub tx_hash: Option<H256>,
+    pub gas_used: Option<U256>,
+    pub timings: Vec<StepTiming>,
+    pub failures: Vec<Failure>,
+}
+
+impl ValidationReport {
+    pub fn passed(&self) -> bool {
+        self.failures.is_empty()
+    }
+
+    pub fn duration(&self) -> Duration {
+        self.timings.iter().map(|timing| timing.duration).sum()
+    }
+}
+
+impl fmt::Display for ValidationReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let status = if self.passed() { "passed" } else { "failed" };
+        write!(f, "validation of {} {} (", self.input, status)?;
+        match self.tx_hash {
+            Some(tx_hash) => write!(f, "tx {:?}", tx_hash)?,
+            None => write!(f, "no tx")?,
+        }
+        if let Some(block_number) = self.block_number {
+            write!(f, ", block {}", block_number)?;
+        }
+        if let Some(gas_used) = self.gas_used {
+            write!(f, ", {} gas", gas_used)?;
+        }
+        writeln!(f, ", {:?})", self.duration())?;
+
+        for failure in self.failures.iter() {
+            writeln!(f, "  - {}", failure)?;
+            match failure {
+                Failure::StateMismatch(diffs) => {
+                    for diff in diffs.iter() {
+                        writeln!(f, "    {}", diff)?;
+                    }
+                }
+                Failure::EventsMismatch => {
+                    writeln!(f, "    expected: {:?}", self.expected_events)?;
+                    writeln!(f, "    actual:   {:?}", self.actual_events)?;
+                }
+                Failure::InvariantViolation { .. }
+                | Failure::UnexpectedRevert { .. }
+                | Failure::MissingRevert { .. }
+                | Failure::RevertReasonMismatch { .. }
+                | Failure::ReceiptStatus { .. }
+                | Failure::ReceiptRecipient { .. }
+                | Failure::LogCount { .. }
+                | Failure::LogTopic { .. }
+                | Failure::LogAddress { .. }
+                | Failure::GasBudgetExceeded { .. } => {}
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn debug_all<T: fmt::Debug>(items: &[T]) -> Vec<String> {
+    items.iter().map(|item| format!("{:?}", item)).collect()
+}
+
+/// Checks a freshly synced `state` against every invariant of the validator.
+pub fn check_invariants<S, T, E, V>(validator: &V, previous: Option<&S>, state: &S) -> Vec<Failure>
 where
     S: State,
     T: StateTransition,
     E: std::fmt::Debug + PartialEq,
     V: Validator<S, T, E>,
 {
+    validator
+        .invariants()
+        .iter()
+        .filter_map(|invariant| {
+            invariant
+                .check(previous, state)
+                .err()
+                .map(|reason| Failure::InvariantViolation {
+                    invariant: invariant.name(),
+                    block: state.get_last_block(),
+                    reason,
+                })
+        })
+        .collect()
+}
+
+/// Checks the receipt of a successful transition against the contract it was sent to and the
+/// events it was expected to emit, decoding its logs with the contract's `abi`.
+pub fn check_receipt<E>(
+    receipt: &TransactionReceipt,
+    recipient: Option<Address>,
+    contract: Address,
+    abi: &Abi,
+    expected_events: &[E],
+) -> Vec<Failure>
+where
+    E: ContractEvents + std::fmt::Debug + PartialEq,
+{
+    let mut failures = vec![];
+
+    if receipt.status != Some(U64::one()) {
+        failures.push(Failure::ReceiptStatus {
+            status: receipt.status,
+        });
+    }
+
+    if recipient != Some(contract) {
+        failures.push(Failure::ReceiptRecipient {
+            expected: contract,
+            actual: recipient,
+        });
+    }
+
+    if receipt.logs.len() != expected_events.len() {
+        failures.push(Failure::LogCount {
+            expected: expected_events.len(),
+            actual: receipt.logs.len(),
+        });
+    }
+
+    for (index, (log, event)) in receipt.logs.iter().zip(expected_events).enumerate() {
+        let topic = log.topics.first().cloned();
+        if topic != Some(event.signature()) {
+            failures.push(Failure::LogTopic {
+                index,
+                expected: event.signature(),
+                actual: topic,
+            });
+        } else {
+            match decode_event::<E>(abi, log.clone()) {
+                Ok(actual) if &actual == event => {}
+                Ok(actual) => failures.push(Failure::LogEvent {
+                    index,
+                    expected: format!("{:?}", event),
+                    actual: format!("{:?}", actual),
+                }),
+                Err(err) => failures.push(Failure::LogDecode {
+                    index,
+                    reason: err.to_string(),
+                }),
+            }
+        }
+
+        if log.address != contract {
+            failures.push(Failure::LogAddress {
+                index,
+                expected: contract,
+                actual: log.address,
+            });
+        }
+    }
+
+    failures
+}
+
+/// Checks how the transaction of a transition ended against what it was expected to do, and
+/// returns the state and events the transition should have left along with any failure.
+///
+/// A successful transaction is checked against its receipt, the `recipient` of the transaction
+/// and the `contract` it was sent to, whose `abi` decodes the receipt's logs, while a reverted
+/// one should leave `initial_state` unchanged and emit no events. No state is expected when the
+/// transaction did not end as expected, as there is then nothing meaningful to compare the
+/// actual state with.
+pub fn check_execution<S, E>(
+    expectation: Expectation<S, E>,
+    execution: &Execution,
+    initial_state: &S,
+    recipient: Option<Address>,
+    contract: Address,
+    abi: &Abi,
+) -> (Option<S>, Vec<E>, Vec<Failure>)
+where
+    S: Clone,
+    E: ContractEvents + std::fmt::Debug + PartialEq,
+{
+    let mut failures = vec![];
+
+    let (expected_state, expected_events) = match (expectation, execution) {
+        (Expectation::Success { state, events }, Execution::Mined(receipt)) => {
+            failures.extend(check_receipt(receipt, recipient, contract, abi, &events));
+            (Some(state), events)
+        }
+        (Expectation::Success { .. }, Execution::Reverted { reason, .. }) => {
+            failures.push(Failure::UnexpectedRevert {
+                reason: reason.clone(),
+            });
+            (None, vec![])
+        }
+        (Expectation::Revert { reason: expected }, Execution::Reverted { reason, .. }) => {
+            if let Some(expected) = expected {
+                if reason.as_ref() != Some(&expected) {
+                    failures.push(Failure::RevertReasonMismatch {
+                        expected,
+                        actual: reason.clone(),
+                    });
+                }
+            }
+            (Some(initial_state.clone()), vec![])
+        }
+        (Expectation::Revert { reason }, Execution::Mined(_)) => {
+            failures.push(Failure::MissingRevert {
+                expected_reason: reason,
+            });
+            (None, vec![])
+        }
+    };
+
+    (expected_state, expected_events, failures)
+}
+
+pub async fn validate<S, T, E, V>(
+    validator: &mut V,
+    input: V::Input,
+) -> Result<ValidationReport, ValidationError>
+where
+    S: State,
+    T: StateTransition,
+    E: ContractEvents + std::fmt::Debug + PartialEq,
+    V: Validator<S, T, E>,
+{
+    let mut timings = vec![];
+    let mut failures = vec![];
+
+    // A validator that has never been synced has no previous state to check invariants against
+    let previous_state = Some(validator.get_state()).filter(|s| s.get_last_block().is_some());
+
     // 1. Sync the Validator's state
+    let started = Instant::now();
     let initial_state = validator.sync_state().await?;
+    failures.extend(check_invariants(
+        validator,
+        previous_state.as_ref(),
+        &initial_state,
+    ));
+    timings.push(StepTiming {
+        step: "sync_initial_state",
+        duration: started.elapsed(),
+    });
 
-    // 2. Transition the Validator's state with a transaction
-    let (expected_state, expected_events) = validator.state_transition(initial_state).await?;
+    // 2. Transition the Validator's state with a transaction, recovering the reason of a
+    //    transaction that was mined but reverted
+    let started = Instant::now();
+    let report_input = format!("{:?}", input);
+    let transition = validator.transition_name(&input);
+    let gas_budget = validator.gas_budget(&input);
+    let TransitionOutcome {
+        expectation,
+        execution,
+    } = validator
+        .state_transition(initial_state.clone(), input)
+        .await?;
+    let execution = execution.recover_reason(validator.client()).await?;
+    timings.push(StepTiming {
+        step: "state_transition",
+        duration: started.elapsed(),
+    });
 
     // 3. Sync the Validator's state
-    validator.sync_state().await?;
+    let started = Instant::now();
+    let actual_state = validator.sync_state().await?;
+    failures.extend(check_invariants(
+        validator,
+        Some(&initial_state),
+        &actual_state,
+    ));
+    timings.push(StepTiming {
+        step: "sync_final_state",
+        duration: started.elapsed(),
+    });
 
-    // 4. If the expected state has a block number, fetch the event logs
-    let events = match expected_state.get_last_block() {
+    // 4. If the transaction was mined, fetch the event logs of its block
+    let started = Instant::now();
+    let receipt = execution.receipt();
+    let block_number = receipt.and_then(|receipt| receipt.block_number);
+    let tx_hash = receipt.map(|receipt| receipt.transaction_hash);
+    let gas_used = receipt.and_then(|receipt| receipt.gas_used);
+    let events = match block_number {
         None => vec![],
         Some(block_number) => validator.sync_events(block_number).await?,
     };
+    timings.push(StepTiming {
+        step: "sync_events",
+        duration: started.elapsed(),
+    });
+
+    // 5. The transaction should have succeeded or reverted as expected. The receipt of a
+    //    successful transaction should match the expected events, and a reverted transaction
+    //    leaves the state unchanged and emits no events.
+    let recipient = match &execution {
+        Execution::Mined(receipt) => {
+            validator
+                .transaction_recipient(receipt.transaction_hash)
+                .await?
+        }
+        Execution::Reverted { .. } => None,
+    };
+    let (expected_state, expected_events, execution_failures) = check_execution(
+        expectation,
+        &execution,
+        &initial_state,
+        recipient,
+        validator.address(),
+        validator.abi(),
+    );
+    failures.extend(execution_failures);
+
+    // 6. Validator's most recent state and events should equal the expected state and events
+    //    from the transition
+    if let Some(expected_state) = expected_state.as_ref() {
+        if &actual_state != expected_state {
+            failures.push(Failure::StateMismatch(expected_state.diff(&actual_state)));
+        }
+
+        if events != expected_events {
+            failures.push(Failure::EventsMismatch);
+        }
+    }
+
+    // 7. The transaction should have used no more gas than the transition's budget
+    if let (Some(budget), Some(gas_used)) = (gas_budget, gas_used) {
+        if gas_used > budget {
+            failures.push(Failure::GasBudgetExceeded { budget, gas_used });
+        }
+    }
+
+    Ok(ValidationReport {
+        transition,
+        input: report_input,
+        expected_state: expected_state
+            .map(|state| format!("{:?}", state))
+            .unwrap_or_default(),
+        actual_state: format!("{:?}", actual_state),
+        expected_events: debug_all(&expected_events),
+        actual_events: debug_all(&events),
+        block_number,
+        tx_hash,
+        gas_used,
+        timings,
+        failures,
+    })
+}
+
+/// Comparison of a state rebuilt from event logs with the state read through view calls.
+#[derive(Clone, Debug)]
+pub struct CrossCheckReport {
+    pub from_block: U64,
+    pub to_block: U64,
+    pub event_count: usize,
+    pub event_state: String,
+    pub view_state: String,
+    /// Fields of the view-call state that differ from the event-derived state
+    pub diffs: Vec<FieldDiff>,
+}
+
+impl CrossCheckReport {
+    pub fn passed(&self) -> bool {
+        self.diffs.is_empty()
+    }
+}
+
+impl fmt::Display for CrossCheckReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let status = if self.passed() { "passed" } else { "failed" };
+        writeln!(
+            f,
+            "cross-check of blocks {} to {} {} ({} events)",
+            self.from_block, self.to_block, status, self.event_count
+        )?;
+        for diff in self.diffs.iter() {
+            writeln!(f, "  - {}", diff)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Rebuilds the state by replaying the events emitted after `from_block` over the state read at
+/// `from_block`, and compares it with the state read from the contract's view functions.
+///
+/// The replay starts from a state read from the contract, usually at its deployment block, as
+/// events describe transitions rather than whole states: a constructor may emit an event without
+/// setting the state the way the same event does after a transition, so replaying every event
+/// over `S::default()` is not sound.
+pub async fn cross_check<S, T, E, V>(
+    validator: &mut V,
+    from_block: U64,
+) -> Result<CrossCheckReport, ValidationError>
+where
+    S: EventSourced<E>,
+    T: StateTransition,
+    E: std::fmt::Debug + PartialEq,
+    V: Validator<S, T, E>,
+{
+    // 1. Read the contract's state at `from_block` and at the most recent block
+    let initial_state = validator.fetch_state_at(from_block).await?;
+    let view_state = validator.fetch_state().await?;
+    let to_block = view_state.get_last_block().unwrap_or(from_block);
+
+    // 2. Collect every event emitted after `from_block`
+    let events = validator
+        .sync_events_range(from_block + 1, to_block)
+        .await?;
+
+    // 3. Fold the events into the initial state
+    let event_state = initial_state.replay(&events);
+
+    Ok(CrossCheckReport {
+        from_block,
+        to_block,
+        event_count: events.len(),
+        event_state: format!("{:?}", event_state),
+        view_state: format!("{:?}", view_state),
+        diffs: event_state.diff(&view_state),
+    })
+}
+
+/// Parameters of a `fuzz` run.
+#[derive(Clone, Debug)]
+pub struct FuzzConfig {
+    /// Seed of the input generator, a failing run is reproduced with the same seed
+    pub seed: u64,
+    /// Number of random sequences to validate
+    pub runs: usize,
+    /// Maximum number of transitions in a sequence
+    pub max_length: usize,
+    /// Maximum number of candidate sequences tried while shrinking
+    pub max_shrinks: usize,
+}
+
+impl Default for FuzzConfig {
+    fn default() -> Self {
+        FuzzConfig {
+            seed: 0,
+            runs: 32,
+            max_length: 8,
+            max_shrinks: 64,
+        }
+    }
+}
+
+/// A failing input sequence found by `fuzz`, along with its shrunk reproducer.
+#[derive(Clone, Debug)]
+pub struct FuzzFailure {
+    pub sequence: Vec<String>,
+    pub minimal_sequence: Vec<String>,
+    /// Report of the failing transition in the minimal sequence
+    pub report: ValidationReport,
+}
+
+#[derive(Clone, Debug)]
+pub struct FuzzReport {
+    pub seed: u64,
+    pub runs: usize,
+    pub failure: Option<FuzzFailure>,
+}
+
+impl FuzzReport {
+    pub fn passed(&self) -> bool {
+        self.failure.is_none()
+    }
+}
+
+/// Validates every input of `sequence` in order, returning the report of the first failing one.
+async fn validate_sequence<S, T, E, V>(
+    validator: &mut V,
+    sequence: &[V::Input],
+) -> Result<Option<ValidationReport>, ValidationError>
+where
+    S: State,
+    T: StateTransition,
+    E: ContractEvents + std::fmt::Debug + PartialEq,
+    V: Validator<S, T, E>,
+{
+    for input in sequence.iter() {
+        let report = validate(validator, input.clone()).await?;
+        if !report.passed() {
+            return Ok(Some(report));
+        }
+    }
+
+    Ok(None)
+}
+
+/// Sequences derived from `sequence` by dropping one input or shrinking one input.
+fn shrink_candidates<I: Clone + Fuzzable>(sequence: &[I]) -> Vec<Vec<I>> {
+    let mut candidates = vec![];
+
+    if sequence.len() > 1 {
+        for i in 0..sequence.len() {
+            let mut candidate = sequence.to_vec();
+            candidate.remove(i);
+            candidates.push(candidate);
+        }
+    }
+
+    for (i, input) in sequence.iter().enumerate() {
+        for shrunk in input.shrink() {
+            let mut candidate = sequence.to_vec();
+            candidate[i] = shrunk;
+            candidates.push(candidate);
+        }
+    }
+
+    candidates
+}
+
+/// Validates random input sequences and shrinks the first failing one to a minimal reproducer.
+///
+/// With a `fixture`, the chain is snapshotted when fuzzing starts and reverted to that snapshot
+/// before every sequence, including the candidates tried while shrinking, so a sequence fails
+/// or passes regardless of the sequences that ran before it. Without one, every sequence runs
+/// on top of the state left by the previous ones, and a reported minimal sequence may only
+/// fail after them.
+pub async fn fuzz<S, T, E, V>(
+    validator: &mut V,
+    config: &FuzzConfig,
+    mut fixture: Option<&mut ChainFixture>,
+) -> Result<FuzzReport, ValidationError>
+where
+    S: State,
+    T: StateTransition,
+    E: ContractEvents + std::fmt::Debug + PartialEq,
+    V: Validator<S, T, E>,
+    V::Input: Fuzzable,
+{
+    if config.max_length == 0 {
+        return Err(ValidationError::InvalidFuzzConfig(
+            "fuzzed sequences need a max_length of at least 1".to_string(),
+        ));
+    }
+
+    if let Some(fixture) = fixture.as_mut() {
+        fixture.snapshot().await?;
+    }
 
-    // 5. Validator's most recent state should equal the expected state from transition
-    assert_eq!(validator.get_state(), expected_state);
+    let mut rng = StdRng::seed_from_u64(config.seed);
 
-    // 6. Validator's most recent events should equal the expected events from transition
-    assert_eq!(events, expected_events);
+    for run in 0..config.runs {
+        // 1. Generate a random sequence of inputs
+        let length = rng.gen_range(1, config.max_length.saturating_add(1));
+        let sequence: Vec<V::Input> = (0..length)
+            .map(|_| <V::Input as Fuzzable>::generate(&mut rng))
+            .collect();
+
+        // 2. Validate the sequence from the snapshot, moving to the next one if it passes
+        reset(&mut fixture).await?;
+        let mut report = match validate_sequence(validator, &sequence).await? {
+            None => continue,
+            Some(report) => report,
+        };
+
+        // 3. Greedily replace the failing sequence with the first failing candidate
+        let mut minimal = sequence.clone();
+        let mut shrinks = 0;
+        'shrink: while shrinks < config.max_shrinks {
+            for candidate in shrink_candidates(&minimal) {
+                if shrinks >= config.max_shrinks {
+                    break 'shrink;
+                }
+                shrinks += 1;
+
+                reset(&mut fixture).await?;
+                if let Some(candidate_report) = validate_sequence(validator, &candidate).await? {
+                    minimal = candidate;
+                    report = candidate_report;
+                    continue 'shrink;
+                }
+            }
+
+            break;
+        }
+
+        return Ok(FuzzReport {
+            seed: config.seed,
+            runs: run + 1,
+            failure: Some(FuzzFailure {
+                sequence: debug_all(&sequence),
+                minimal_sequence: debug_all(&minimal),
+                report,
+            }),
+        });
+    }
+
+    Ok(FuzzReport {
+        seed: config.seed,
+        runs: config.runs,
+        failure: None,
+    })
+}
+
+/// Reverts the chain to the fixture's snapshot, if fuzzing with a fixture.
+async fn reset(fixture: &mut Option<&mut ChainFixture>) -> Result<(), ValidationError> {
+    if let Some(fixture) = fixture {
+        fixture.reset().await?;
+    }
 
     Ok(())
 }
@@ -37,81 +801,332 @@ where
 #[cfg(test)]
 mod test {
     use super::*;
-    use ethers::{
-        prelude::*,
-        utils::{Ganache, Solc},
-    };
-    use std::{convert::TryFrom, sync::Arc, time::Duration};
 
-    use crate::{simple_storage_validator::SimpleStorageValidator, ValidatorBase, ValidatorConfig};
-
-    abigen!(SimpleContract, "./contract-abi/SimpleStorage.json");
+    use crate::{
+        fixture::SimpleStorageChain,
+        simple_storage::ValueChangedFilter,
+        simple_storage_validator::{
+            SimpleStorageEvent, SimpleStorageInput, SimpleStorageValidator,
+        },
+        ValidatorBase,
+    };
 
     #[tokio::test]
-    #[ignore = "Include only to generate bindings for a contract"]
-    async fn test_abigen() {
-        Abigen::new("SimpleStorage", "./contract-abi/SimpleStorage.json")
-            .unwrap()
-            .generate()
+    async fn test_validate_deploy() {
+        // 1. launch a local chain and deploy the contract
+        let chain = SimpleStorageChain::deploy().await;
+
+        // 2. create new validator
+        let mut validator = chain.validator(0);
+        let deploy_block = validator
+            .fetch_state()
+            .await
             .unwrap()
-            .write_to_file("./simple_storage.rs")
+            .get_last_block()
             .unwrap();
+
+        // 3. validate
+        let first_report = validate(&mut validator, Default::default()).await.unwrap();
+        assert!(first_report.passed(), "{}", first_report);
+
+        // 4. validate a transition with a different input
+        let input = SimpleStorageInput::SetValue("bye".to_string());
+        let report = validate(&mut validator, input).await.unwrap();
+        assert!(report.passed(), "{}", report);
+
+        // 5. query the state as it was after the first transition
+        let state = validator
+            .fetch_state_at(first_report.block_number.unwrap())
+            .await
+            .unwrap();
+        assert_eq!(format!("{:?}", state), first_report.actual_state);
+
+        // 6. rebuild the state from the events of both transitions
+        let report = cross_check(&mut validator, deploy_block).await.unwrap();
+        assert!(report.passed(), "{}", report);
+        assert_eq!(report.event_count, 2);
     }
 
     #[tokio::test]
-    async fn test_validate_deploy() {
-        let compiled = Solc::new("./contract-src/SimpleStorage.sol")
-            .build()
+    async fn test_cross_check() {
+        let chain = SimpleStorageChain::deploy().await;
+        let mut validator = chain.validator(0);
+        let mut sender = chain.validator(1);
+
+        let deploy_block = validator
+            .fetch_state()
+            .await
+            .unwrap()
+            .get_last_block()
             .unwrap();
-        let contract = compiled
-            .get("SimpleStorage")
-            .expect("could not find contract");
 
-        // 2. launch ganache
-        let ganache = Ganache::new().spawn();
+        // 1. Straight after deployment, the constructor's event is not replayed
+        let report = cross_check(&mut validator, deploy_block).await.unwrap();
+        assert!(report.passed(), "{}", report);
+        assert_eq!(report.event_count, 0);
+        assert_eq!(report.to_block, deploy_block);
 
-        // 3. instantiate our wallet
-        let wallet: Wallet = ganache.keys()[0].clone().into();
+        // 2. Both transitions rebuild the state read from the contract
+        for value in ["hi", "bye"].iter() {
+            let input = SimpleStorageInput::SetValue(value.to_string());
+            let report = validate(&mut sender, input).await.unwrap();
+            assert!(report.passed(), "{}", report);
+        }
 
-        // 4. connect to the network
-        let provider = Provider::<Http>::try_from(ganache.endpoint())
-            .unwrap()
-            .interval(Duration::from_millis(10u64));
+        let report = cross_check(&mut validator, deploy_block).await.unwrap();
+        assert!(report.passed(), "{}", report);
+        assert_eq!(report.event_count, 2);
+        assert_eq!(report.to_block, deploy_block + 2);
+    }
 
-        // 5. instantiate the client with the wallet
-        let client = wallet.clone().connect(provider);
-        let client = Arc::new(client);
+    #[test]
+    fn test_shrink_candidates() {
+        let sequence = vec![
+            SimpleStorageInput::SetValue("ab".to_string()),
+            SimpleStorageInput::SetValue("".to_string()),
+        ];
 
-        // 6. create a factory which will be used to deploy instances of the contract
-        let factory = ContractFactory::new(
-            contract.abi.clone(),
-            contract.bytecode.clone(),
-            client.clone(),
+        assert_eq!(
+            shrink_candidates(&sequence),
+            vec![
+                vec![SimpleStorageInput::SetValue("".to_string())],
+                vec![SimpleStorageInput::SetValue("ab".to_string())],
+                vec![
+                    SimpleStorageInput::SetValue("".to_string()),
+                    SimpleStorageInput::SetValue("".to_string()),
+                ],
+                vec![
+                    SimpleStorageInput::SetValue("a".to_string()),
+                    SimpleStorageInput::SetValue("".to_string()),
+                ],
+            ]
         );
+    }
+
+    /// The ABI `SimpleStorage` is bound with.
+    fn simple_storage_abi() -> Abi {
+        serde_json::from_str(include_str!("../contract-abi/SimpleStorage.json")).unwrap()
+    }
+
+    #[test]
+    fn test_check_receipt() {
+        let contract = Address::from_low_u64_be(1);
+        let abi = simple_storage_abi();
+        let receipt = TransactionReceipt {
+            status: Some(U64::one()),
+            ..Default::default()
+        };
+        let events: Vec<SimpleStorageEvent> = vec![];
+        assert!(check_receipt(&receipt, Some(contract), contract, &abi, &events).is_empty());
 
-        // 7. deploy it with the constructor arguments
-        let deployer = factory.deploy("initial value".to_string()).unwrap();
-        let contract = deployer.clone().send().await.unwrap();
+        let receipt = TransactionReceipt {
+            status: Some(U64::zero()),
+            ..Default::default()
+        };
+        let events = vec![SimpleStorageEvent::ValueChanged(ValueChangedFilter {
+            author: Address::zero(),
+            old_author: Address::zero(),
+            old_value: "".to_string(),
+            new_value: "hi".to_string(),
+        })];
+        assert_eq!(
+            check_receipt(&receipt, None, contract, &abi, &events),
+            vec![
+                Failure::ReceiptStatus {
+                    status: Some(U64::zero())
+                },
+                Failure::ReceiptRecipient {
+                    expected: contract,
+                    actual: None,
+                },
+                Failure::LogCount {
+                    expected: 1,
+                    actual: 0,
+                },
+            ]
+        );
+    }
 
-        // 8. get validator config
-        let validator_config = ValidatorConfig {
-            wallet: wallet.clone(),
-            address: contract.address(),
-            url: ganache.endpoint().into(),
+    #[tokio::test]
+    async fn test_check_receipt_events() {
+        let chain = SimpleStorageChain::deploy().await;
+        let mut sender = chain.validator(1);
+        let input = SimpleStorageInput::SetValue("hi".to_string());
+        let report = validate(&mut sender, input).await.unwrap();
+        assert!(report.passed(), "{}", report);
+        let receipt = sender
+            .client()
+            .get_transaction_receipt(report.tx_hash.unwrap())
+            .await
+            .unwrap();
+
+        let event = |author: Address| {
+            SimpleStorageEvent::ValueChanged(ValueChangedFilter {
+                author,
+                old_author: Address::zero(),
+                old_value: "initial value".to_string(),
+                new_value: "hi".to_string(),
+            })
+        };
+        let check = |expected: SimpleStorageEvent| {
+            check_receipt(
+                &receipt,
+                Some(chain.address),
+                chain.address,
+                sender.abi(),
+                &[expected],
+            )
+        };
+        let author = sender.client().address();
+        assert!(check(event(author)).is_empty());
+
+        // The log has the expected signature, but was emitted for another author
+        let other = chain.fixture.wallet(0).address();
+        assert_eq!(
+            check(event(other)),
+            vec![Failure::LogEvent {
+                index: 0,
+                expected: format!("{:?}", event(other)),
+                actual: format!("{:?}", event(author)),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_check_execution() {
+        let contract = Address::from_low_u64_be(1);
+        let abi = simple_storage_abi();
+        let receipt = TransactionReceipt {
+            status: Some(U64::one()),
+            ..Default::default()
+        };
+        let mined = Execution::Mined(receipt);
+        let reverted = Execution::Reverted {
+            reason: Some("not the owner".to_string()),
+            receipt: None,
+        };
+        let initial_state = "initial".to_string();
+        let success = || Expectation::<String, SimpleStorageEvent>::Success {
+            state: "expected".to_string(),
+            events: vec![],
         };
+        let revert = |reason: &str| Expectation::<String, SimpleStorageEvent>::Revert {
+            reason: Some(reason.to_string()),
+        };
+
+        // A successful transaction is expected to leave the expected state
+        let (state, events, failures) = check_execution(
+            success(),
+            &mined,
+            &initial_state,
+            Some(contract),
+            contract,
+            &abi,
+        );
+        assert_eq!(state, Some("expected".to_string()));
+        assert!(events.is_empty());
+        assert!(failures.is_empty());
+
+        let (state, _, failures) =
+            check_execution(success(), &reverted, &initial_state, None, contract, &abi);
+        assert_eq!(state, None);
+        assert_eq!(
+            failures,
+            vec![Failure::UnexpectedRevert {
+                reason: Some("not the owner".to_string())
+            }]
+        );
+
+        // A reverted transaction is expected to leave the initial state
+        let (state, events, failures) = check_execution(
+            revert("not the owner"),
+            &reverted,
+            &initial_state,
+            None,
+            contract,
+            &abi,
+        );
+        assert_eq!(state, Some("initial".to_string()));
+        assert!(events.is_empty());
+        assert!(failures.is_empty());
 
-        // 9. create new validator
-        let mut validator = SimpleStorageValidator::init_with(validator_config);
+        let (state, _, failures) = check_execution(
+            revert("not allowed"),
+            &reverted,
+            &initial_state,
+            None,
+            contract,
+            &abi,
+        );
+        assert_eq!(state, Some("initial".to_string()));
+        assert_eq!(
+            failures,
+            vec![Failure::RevertReasonMismatch {
+                expected: "not allowed".to_string(),
+                actual: Some("not the owner".to_string()),
+            }]
+        );
 
-        // 10. validate
-        validate(&mut validator).await.unwrap();
+        let (state, _, failures) = check_execution(
+            revert("not the owner"),
+            &mined,
+            &initial_state,
+            Some(contract),
+            contract,
+            &abi,
+        );
+        assert_eq!(state, None);
+        assert_eq!(
+            failures,
+            vec![Failure::MissingRevert {
+                expected_reason: Some("not the owner".to_string())
+            }]
+        );
     }
 
     #[tokio::test]
     #[ignore = "Include only when running ganache with a deployed instance of SimpleStorage"]
     async fn test_validate_dev() {
-        let mut validator = SimpleStorageValidator::init();
+        let mut validator = SimpleStorageValidator::init().unwrap();
+
+        let report = validate(&mut validator, Default::default()).await.unwrap();
+        assert!(report.passed(), "{}", report);
+    }
+
+    #[tokio::test]
+    async fn test_fuzz() {
+        let mut chain = SimpleStorageChain::deploy().await;
+        let mut validator = chain.validator(0);
+        let config = FuzzConfig {
+            seed: 7,
+            runs: 4,
+            max_length: 3,
+            max_shrinks: 8,
+        };
+
+        let block = validator.client().get_block_number().await.unwrap();
+        let report = fuzz(&mut validator, &config, Some(&mut chain.fixture))
+            .await
+            .unwrap();
+        assert!(report.passed(), "{:?}", report.failure);
+        assert_eq!((report.seed, report.runs), (7, 4));
+
+        // every sequence was reverted before the next, so only the last one is still mined
+        let mined = validator.client().get_block_number().await.unwrap() - block;
+        assert!(mined.as_u64() <= config.max_length as u64);
+    }
+
+    #[tokio::test]
+    async fn test_fuzz_rejects_empty_sequences() {
+        let mut validator = SimpleStorageValidator::init().unwrap();
+        let config = FuzzConfig {
+            max_length: 0,
+            ..Default::default()
+        };
 
-        validate(&mut validator).await.unwrap();
+        assert!(matches!(
+            fuzz(&mut validator, &config, None).await,
+            Err(ValidationError::InvalidFuzzConfig(_))
+        ));
     }
 }
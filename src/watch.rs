@@ -0,0 +1,447 @@
+use ethers::core::types::U64;
+use tokio::time::delay_for;
+
+use std::{collections::BTreeMap, fmt, time::Duration};
+
+use crate::{
+    validate::{check_invariants, Failure},
+    EventSourced, State, StateDiff, StateTransition, ValidationError, Validator,
+};
+
+/// Parameters of a `watch` run.
+#[derive(Clone, Debug)]
+pub struct WatchConfig {
+    /// Time to wait between two polls for new blocks
+    pub poll_interval: Duration,
+    /// Stop once this block has been checked, or follow the chain forever when `None`
+    pub until_block: Option<U64>,
+    /// Number of synced blocks kept to roll back to after a chain reorganization
+    pub reorg_depth: usize,
+}
+
+impl Default for WatchConfig {
+    fn default() -> Self {
+        WatchConfig {
+            poll_interval: Duration::from_secs(1),
+            until_block: None,
+            reorg_depth: 64,
+        }
+    }
+}
+
+/// A block at which the modelled state diverged from the contract's state.
+#[derive(Clone, Debug)]
+pub struct Divergence {
+    pub block: U64,
+    pub model_state: String,
+    pub actual_state: String,
+    pub failures: Vec<Failure>,
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "divergence at block {}", self.block)?;
+        for failure in self.failures.iter() {
+            writeln!(f, "  - {}", failure)?;
+            if let Failure::StateMismatch(diffs) = failure {
+                for diff in diffs.iter() {
+                    writeln!(f, "    {}", diff)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// A chain reorganization detected while watching.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Reorg {
+    /// Last synced block that is still part of the canonical chain, or `None` when none of the
+    /// cached blocks are
+    pub fork_block: Option<U64>,
+    /// Number of synced blocks that were rolled back
+    pub depth: u64,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct WatchReport {
+    pub divergences: Vec<Divergence>,
+    pub reorgs: Vec<Reorg>,
+}
+
+impl WatchReport {
+    pub fn passed(&self) -> bool {
+        self.divergences.is_empty()
+    }
+}
+
+/// The contract's state and the modelled state after a synced block.
+struct SyncedBlock<S> {
+    state: S,
+    model: S,
+}
+
+enum ForkPoint {
+    /// The last synced block is still canonical
+    Unchanged,
+    /// Synced blocks after this one are no longer canonical
+    At(U64),
+    /// None of the cached blocks are canonical anymore
+    BeyondCache,
+}
+
+/// Walks back from the last synced block to the most recent one whose hash is still canonical.
+///
+/// A block the node no longer has, as after a reorganization to a shorter chain, is not canonical.
+async fn find_fork_point<S, T, E, V>(
+    validator: &V,
+    synced: &BTreeMap<U64, SyncedBlock<S>>,
+) -> Result<ForkPoint, ValidationError>
+where
+    S: EventSourced<E>,
+    T: StateTransition,
+    E: std::fmt::Debug + PartialEq,
+    V: Validator<S, T, E>,
+{
+    for (depth, (block, synced_block)) in synced.iter().rev().enumerate() {
+        let canonical_hash = validator.block_hash(*block).await?;
+        if canonical_hash.is_some() && canonical_hash == synced_block.state.get_last_block_hash() {
+            return Ok(match depth {
+                0 => ForkPoint::Unchanged,
+                _ => ForkPoint::At(*block),
+            });
+        }
+    }
+
+    Ok(ForkPoint::BeyondCache)
+}
+
+/// The blocks synced while watching a contract, and what was found in them.
+struct Watcher<S> {
+    synced: BTreeMap<U64, SyncedBlock<S>>,
+    last_block: U64,
+    report: WatchReport,
+}
+
+impl<S> Watcher<S> {
+    /// Seeds the model with the contract's current state.
+    async fn start<T, E, V>(validator: &mut V) -> Result<Self, ValidationError>
+    where
+        S: EventSourced<E>,
+        T: StateTransition,
+        E: std::fmt::Debug + PartialEq,
+        V: Validator<S, T, E>,
+    {
+        let state = validator.sync_state().await?;
+        let last_block = state.get_last_block().unwrap_or_default();
+        let mut synced = BTreeMap::new();
+        synced.insert(
+            last_block,
+            SyncedBlock {
+                state: state.clone(),
+                model: state,
+            },
+        );
+
+        Ok(Watcher {
+            synced,
+            last_block,
+            report: WatchReport::default(),
+        })
+    }
+
+    /// Rolls back the blocks that are no longer canonical, then checks every block mined since
+    /// the last poll, up to `until_block`.
+    async fn poll<T, E, V, F>(
+        &mut self,
+        validator: &mut V,
+        config: &WatchConfig,
+        on_divergence: &mut F,
+    ) -> Result<(), ValidationError>
+    where
+        S: EventSourced<E>,
+        T: StateTransition,
+        E: std::fmt::Debug + PartialEq,
+        V: Validator<S, T, E>,
+        F: FnMut(&Divergence),
+    {
+        // 1. Roll back the blocks that are no longer part of the canonical chain
+        match find_fork_point(validator, &self.synced).await? {
+            ForkPoint::Unchanged => {}
+            ForkPoint::At(fork_block) => {
+                self.synced.split_off(&(fork_block + 1));
+                self.report.reorgs.push(Reorg {
+                    fork_block: Some(fork_block),
+                    depth: (self.last_block - fork_block).as_u64(),
+                });
+                self.last_block = fork_block;
+            }
+            ForkPoint::BeyondCache => {
+                // Nothing to roll back to, start over from the contract's current state
+                let state = validator.sync_state().await?;
+                let head = state.get_last_block().unwrap_or_default();
+                self.report.reorgs.push(Reorg {
+                    fork_block: None,
+                    depth: self.synced.len() as u64,
+                });
+                self.synced.clear();
+                self.synced.insert(
+                    head,
+                    SyncedBlock {
+                        state: state.clone(),
+                        model: state,
+                    },
+                );
+                self.last_block = head;
+            }
+        }
+
+        // 2. Sync the contract's state, skipping the poll if no new block was mined, and never
+        //    checking blocks past `until_block`
+        let head = match validator.sync_state().await?.get_last_block() {
+            Some(head) if head > self.last_block => head,
+            _ => return Ok(()),
+        };
+        let head = config
+            .until_block
+            .map_or(head, |until_block| head.min(until_block));
+
+        let mut previous_state = self.synced[&self.last_block].state.clone();
+        let mut model = self.synced[&self.last_block].model.clone();
+
+        // 3. Query the events of every new block at once
+        let mut block = self.last_block + 1;
+        let mut block_events: BTreeMap<U64, Vec<E>> = BTreeMap::new();
+        for (event_block, event) in validator.query_events(block, head).await? {
+            block_events.entry(event_block).or_default().push(event);
+        }
+
+        while block <= head {
+            // 4. Apply the block's events to the model
+            let events = block_events.remove(&block).unwrap_or_default();
+            model = model.replay(&events);
+
+            // 5. The model should equal the contract's state at the block, and both should
+            //    hold the invariants
+            let state = validator.fetch_state_at(block).await?;
+            let mut failures = check_invariants(validator, Some(&previous_state), &state);
+            if model != state {
+                failures.insert(0, Failure::StateMismatch(model.diff(&state)));
+            }
+
+            if !failures.is_empty() {
+                let divergence = Divergence {
+                    block,
+                    model_state: format!("{:?}", model),
+                    actual_state: format!("{:?}", state),
+                    failures,
+                };
+                on_divergence(&divergence);
+                self.report.divergences.push(divergence);
+
+                model = state.clone();
+            }
+
+            self.synced.insert(
+                block,
+                SyncedBlock {
+                    state: state.clone(),
+                    model: model.clone(),
+                },
+            );
+            previous_state = state;
+            block = block + 1;
+        }
+
+        self.last_block = head;
+
+        // 6. Only keep as many blocks as a reorganization is expected to roll back
+        while self.synced.len() > config.reorg_depth.max(1) {
+            let oldest = *self.synced.keys().next().expect("the cache is not empty");
+            self.synced.remove(&oldest);
+        }
+
+        Ok(())
+    }
+}
+
+/// Follows new blocks and validates transactions sent by third parties.
+///
+/// The events of every new block are applied to a model of the contract's state, which is then
+/// compared with the contract's state at that block. `on_divergence` is called for every block
+/// at which they differ, or at which an invariant breaks, after which the model is reset to the
+/// contract's state so that one divergence is only reported once.
+///
+/// The states of the last `reorg_depth` blocks are cached along with their block hash. When a
+/// cached hash is no longer canonical, the cache is rolled back to the fork point and the blocks
+/// after it are synced again.
+pub async fn watch<S, T, E, V, F>(
+    validator: &mut V,
+    config: &WatchConfig,
+    mut on_divergence: F,
+) -> Result<WatchReport, ValidationError>
+where
+    S: EventSourced<E>,
+    T: StateTransition,
+    E: std::fmt::Debug + PartialEq,
+    V: Validator<S, T, E>,
+    F: FnMut(&Divergence),
+{
+    let mut watcher = Watcher::start(validator).await?;
+
+    loop {
+        if let Some(until_block) = config.until_block {
+            if watcher.last_block >= until_block {
+                break;
+            }
+        }
+
+        delay_for(config.poll_interval).await;
+        watcher.poll(validator, config, &mut on_divergence).await?;
+    }
+
+    Ok(watcher.report)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::{
+        fixture::SimpleStorageChain,
+        simple_storage_validator::{SimpleStorageInput, SimpleStorageValidator},
+        validate::validate,
+    };
+
+    #[tokio::test]
+    async fn test_watch_third_party_transactions() {
+        let chain = SimpleStorageChain::deploy().await;
+        let mut watcher = chain.validator(0);
+        let mut sender = chain.validator(1);
+
+        // Watch until both transactions of the second wallet are mined
+        let start = watcher
+            .fetch_state()
+            .await
+            .unwrap()
+            .get_last_block()
+            .unwrap();
+        let config = WatchConfig {
+            poll_interval: Duration::from_millis(10),
+            until_block: Some(start + 2),
+            ..Default::default()
+        };
+        let send = async {
+            for value in ["a", "b"].iter() {
+                let input = SimpleStorageInput::SetValue(value.to_string());
+                let report = validate(&mut sender, input).await.unwrap();
+                assert!(report.passed(), "{}", report);
+            }
+        };
+        let (report, ()) = futures::join!(watch(&mut watcher, &config, |_| {}), send);
+
+        let report = report.unwrap();
+        assert!(report.passed(), "{:?}", report.divergences);
+        assert!(report.reorgs.is_empty());
+        assert!(format!("{:?}", watcher.get_state()).contains("value: \"b\""));
+    }
+
+    async fn set_value(sender: &mut SimpleStorageValidator, value: &str) {
+        let input = SimpleStorageInput::SetValue(value.to_string());
+        let report = validate(sender, input).await.unwrap();
+        assert!(report.passed(), "{}", report);
+    }
+
+    fn synced_value<S: fmt::Debug>(watcher: &Watcher<S>, block: U64) -> String {
+        format!("{:?}", watcher.synced[&block].state)
+    }
+
+    #[tokio::test]
+    async fn test_watch_reorg() {
+        let mut chain = SimpleStorageChain::deploy().await;
+        chain.fixture.snapshot().await.unwrap();
+        let mut validator = chain.validator(0);
+        let mut sender = chain.validator(1);
+        let config = WatchConfig::default();
+        let mut on_divergence = |_: &Divergence| {};
+
+        // 1. Sync a block on top of the snapshot
+        let mut watcher = Watcher::start(&mut validator).await.unwrap();
+        let fork_block = watcher.last_block;
+        set_value(&mut sender, "a").await;
+        watcher
+            .poll(&mut validator, &config, &mut on_divergence)
+            .await
+            .unwrap();
+        assert!(synced_value(&watcher, fork_block + 1).contains("value: \"a\""));
+
+        // 2. Fork the chain from the snapshot with two different blocks
+        chain.fixture.reset().await.unwrap();
+        set_value(&mut sender, "b").await;
+        set_value(&mut sender, "c").await;
+        watcher
+            .poll(&mut validator, &config, &mut on_divergence)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            watcher.report.reorgs,
+            vec![Reorg {
+                fork_block: Some(fork_block),
+                depth: 1,
+            }]
+        );
+        assert!(watcher.report.divergences.is_empty());
+        assert_eq!(watcher.last_block, fork_block + 2);
+        assert!(synced_value(&watcher, fork_block + 1).contains("value: \"b\""));
+        assert!(synced_value(&watcher, fork_block + 2).contains("value: \"c\""));
+    }
+
+    #[tokio::test]
+    async fn test_watch_reorg_to_shorter_chain() {
+        let mut chain = SimpleStorageChain::deploy().await;
+        chain.fixture.snapshot().await.unwrap();
+        let mut validator = chain.validator(0);
+        let mut sender = chain.validator(1);
+        let config = WatchConfig::default();
+        let mut on_divergence = |_: &Divergence| {};
+
+        // 1. Sync a block on top of the snapshot
+        let mut watcher = Watcher::start(&mut validator).await.unwrap();
+        let fork_block = watcher.last_block;
+        set_value(&mut sender, "a").await;
+        watcher
+            .poll(&mut validator, &config, &mut on_divergence)
+            .await
+            .unwrap();
+
+        // 2. Revert to the snapshot, leaving the chain one block shorter than the synced one
+        chain.fixture.reset().await.unwrap();
+        watcher
+            .poll(&mut validator, &config, &mut on_divergence)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            watcher.report.reorgs,
+            vec![Reorg {
+                fork_block: Some(fork_block),
+                depth: 1,
+            }]
+        );
+        assert_eq!(watcher.last_block, fork_block);
+        assert!(!watcher.synced.contains_key(&(fork_block + 1)));
+
+        // 3. The next block is synced on top of the fork point
+        set_value(&mut sender, "b").await;
+        watcher
+            .poll(&mut validator, &config, &mut on_divergence)
+            .await
+            .unwrap();
+
+        assert_eq!(watcher.report.reorgs.len(), 1);
+        assert!(watcher.report.divergences.is_empty());
+        assert!(synced_value(&watcher, fork_block + 1).contains("value: \"b\""));
+    }
+}
@@ -5,8 +5,8 @@ use proc_macro::TokenStream;
 use proc_macro2::{Ident, Span};
 use quote::{quote, ToTokens};
 use syn::{
-    parse, parse_macro_input, punctuated::Punctuated, token::Comma, Data, DataStruct, Field,
-    Fields, FieldsNamed, ItemStruct,
+    parse, parse_macro_input, punctuated::Punctuated, token::Comma, Attribute, Data, DataStruct,
+    Field, Fields, FieldsNamed, ItemStruct, Lit, Meta, MetaNameValue, NestedMeta, Path, Type,
 };
 
 #[proc_macro_derive(BaseState)]
@@ -19,6 +19,7 @@ fn impl_base_state(ast: &syn::DeriveInput) -> TokenStream {
     let name = &ast.ident;
 
     let block_ident = Some(Ident::new("last_block", Span::call_site()));
+    let block_hash_ident = Some(Ident::new("last_block_hash", Span::call_site()));
     let fields = match &ast.data {
         Data::Struct(DataStruct {
             fields: Fields::Named(fields),
@@ -28,7 +29,7 @@ fn impl_base_state(ast: &syn::DeriveInput) -> TokenStream {
     };
     let field_name: Vec<&Option<Ident>> = fields
         .into_iter()
-        .filter(|field| !field.ident.eq(&block_ident))
+        .filter(|field| !field.ident.eq(&block_ident) && !field.ident.eq(&block_hash_ident))
         .map(|field| &field.ident)
         .collect();
 
@@ -41,6 +42,10 @@ fn impl_base_state(ast: &syn::DeriveInput) -> TokenStream {
             fn get_last_block(&self) -> Option<U64> {
                 self.last_block
             }
+
+            fn get_last_block_hash(&self) -> Option<H256> {
+                self.last_block_hash
+            }
         }
 
         impl PartialEq for #name {
@@ -54,6 +59,215 @@ fn impl_base_state(ast: &syn::DeriveInput) -> TokenStream {
                 true
             }
         }
+
+        impl StateDiff for #name {
+            fn diff(&self, actual: &Self) -> Vec<FieldDiff> {
+                let mut diffs = vec![];
+                #(
+                    if self.#field_name != actual.#field_name {
+                        diffs.push(FieldDiff {
+                            field: stringify!(#field_name),
+                            expected: format!("{:?}", self.#field_name),
+                            actual: format!("{:?}", actual.#field_name),
+                        });
+                    }
+                )*
+
+                diffs
+            }
+        }
+    };
+
+    gen.into()
+}
+
+#[proc_macro_derive(FetchState, attributes(hachiko, view))]
+pub fn fetch_state_derive(input: TokenStream) -> TokenStream {
+    let ast = syn::parse(input).unwrap();
+    impl_fetch_state(&ast)
+}
+
+/// A `contract` argument of a #[hachiko(...)] attribute, naming the contract binding.
+///
+/// FetchState and ValidatorBase share this grammar. A state does not hold its binding, so its
+/// struct gives the binding type, e.g. #[hachiko(contract = "SimpleStorage<Http, Wallet>")]. A
+/// validator holds its binding in a field, so the field is marked with #[hachiko(contract)] and
+/// the binding type is the type of the field.
+struct ContractArg {
+    /// The argument, for errors to point at
+    arg: NestedMeta,
+    /// The binding type, when given as a string
+    ty: Option<Type>,
+}
+
+/// Parses the #[hachiko(...)] attributes among `attrs`, rejecting any unknown argument.
+fn contract_args(attrs: &[Attribute]) -> syn::Result<Vec<ContractArg>> {
+    let mut args = vec![];
+    for attr in attrs.iter().filter(|attr| attr.path.is_ident("hachiko")) {
+        let list = match attr.parse_meta()? {
+            Meta::List(list) => list,
+            meta => {
+                return Err(syn::Error::new_spanned(
+                    meta,
+                    "expected #[hachiko(contract)] or #[hachiko(contract = \"...\")]",
+                ))
+            }
+        };
+        for arg in list.nested {
+            let ty = match &arg {
+                NestedMeta::Meta(Meta::Path(path)) if path.is_ident("contract") => None,
+                NestedMeta::Meta(Meta::NameValue(MetaNameValue {
+                    path,
+                    lit: Lit::Str(lit),
+                    ..
+                })) if path.is_ident("contract") => Some(lit.parse::<Type>()?),
+                _ => {
+                    return Err(syn::Error::new_spanned(
+                        arg,
+                        "unknown hachiko argument, expected `contract` or `contract = \"...\"`",
+                    ))
+                }
+            };
+            args.push(ContractArg { arg, ty });
+        }
+    }
+
+    Ok(args)
+}
+
+/// The contract binding type given with #[hachiko(contract = "...")] on the state struct.
+fn contract_type(ast: &syn::DeriveInput, fields: &Punctuated<Field, Comma>) -> syn::Result<Type> {
+    for field in fields {
+        if let Some(ContractArg { arg, .. }) = contract_args(&field.attrs)?.into_iter().next() {
+            return Err(syn::Error::new_spanned(
+                arg,
+                "a state does not hold its contract binding, give the binding type on the \
+                 struct with #[hachiko(contract = \"...\")]",
+            ));
+        }
+    }
+
+    let mut args = contract_args(&ast.attrs)?;
+    if let Some(ContractArg { arg, .. }) = args.get(1) {
+        return Err(syn::Error::new_spanned(
+            arg,
+            "the contract binding type is given more than once",
+        ));
+    }
+    match args.pop() {
+        Some(ContractArg { ty: Some(ty), .. }) => Ok(ty),
+        Some(ContractArg { arg, ty: None }) => Err(syn::Error::new_spanned(
+            arg,
+            "expected the contract binding type, e.g. \
+             #[hachiko(contract = \"SimpleStorage<Http, Wallet>\")]",
+        )),
+        None => Err(syn::Error::new_spanned(
+            &ast.ident,
+            "expected the contract binding type, e.g. \
+             #[hachiko(contract = \"SimpleStorage<Http, Wallet>\")]",
+        )),
+    }
+}
+
+/// How a state field is fetched, as given with #[view(...)].
+enum View {
+    /// Set to the result of the view function, e.g. #[view(get_value)]
+    Function(Ident),
+    /// Left to its default value, with #[view(skip)]
+    Skip,
+}
+
+fn view(field: &Field) -> syn::Result<Option<View>> {
+    let attr = match field.attrs.iter().find(|attr| attr.path.is_ident("view")) {
+        Some(attr) => attr,
+        None => return Ok(None),
+    };
+    let function = attr.parse_args::<Ident>().map_err(|_| {
+        syn::Error::new_spanned(
+            attr,
+            "expected a view function, e.g. #[view(get_value)], or #[view(skip)]",
+        )
+    })?;
+
+    if function == "skip" {
+        Ok(Some(View::Skip))
+    } else {
+        Ok(Some(View::Function(function)))
+    }
+}
+
+fn impl_fetch_state(ast: &syn::DeriveInput) -> TokenStream {
+    let name = &ast.ident;
+
+    let block_ident = Some(Ident::new("last_block", Span::call_site()));
+    let block_hash_ident = Some(Ident::new("last_block_hash", Span::call_site()));
+    let fields = match &ast.data {
+        Data::Struct(DataStruct {
+            fields: Fields::Named(fields),
+            ..
+        }) => &fields.named,
+        _ => panic!("expected a struct with named fields"),
+    };
+    let contract_type = match contract_type(ast, fields) {
+        Ok(contract_type) => contract_type,
+        Err(err) => return err.to_compile_error().into(),
+    };
+
+    // Every field is either fetched from the contract or explicitly skipped, so that a forgotten
+    // annotation does not silently leave a field to its default value
+    let mut view_field_name = vec![];
+    let mut view_function = vec![];
+    let mut default_field_name = vec![];
+    for field in fields
+        .iter()
+        .filter(|field| !field.ident.eq(&block_ident) && !field.ident.eq(&block_hash_ident))
+    {
+        match view(field) {
+            Err(err) => return err.to_compile_error().into(),
+            Ok(Some(View::Function(function))) => {
+                view_field_name.push(&field.ident);
+                view_function.push(function);
+            }
+            Ok(Some(View::Skip)) => default_field_name.push(&field.ident),
+            Ok(None) => {
+                return syn::Error::new_spanned(
+                    field,
+                    "expected the view function fetching the field, e.g. #[view(get_value)], or \
+                     #[view(skip)] to leave it to its default value",
+                )
+                .to_compile_error()
+                .into()
+            }
+        }
+    }
+
+    let gen = quote! {
+        impl #name {
+            /// Fetches the state at the most recent block.
+            pub async fn fetch_state(contract: &#contract_type) -> Result<Self, ContractError> {
+                let block = contract.client().get_block_number().await?;
+
+                Self::fetch_state_at(contract, block).await
+            }
+
+            /// Fetches the state with every view function called at `block`.
+            pub async fn fetch_state_at(
+                contract: &#contract_type,
+                block: U64,
+            ) -> Result<Self, ContractError> {
+                let last_block_hash = contract.client().get_block(block).await?.hash;
+                #(
+                    let #view_field_name = contract.#view_function().block(block).call().await?;
+                )*
+
+                Ok(#name {
+                    #( #view_field_name, )*
+                    #( #default_field_name: Default::default(), )*
+                    last_block: Some(block),
+                    last_block_hash,
+                })
+            }
+        }
     };
 
     gen.into()
@@ -88,19 +302,29 @@ pub fn configurable_derive(input: TokenStream) -> TokenStream {
 fn impl_fetch_config(ast: &syn::DeriveInput) -> TokenStream {
     let name = &ast.ident;
     let config_filename = to_snake_case(&name.to_string());
-    let config_path = format!("config/default/{}", config_filename);
+    let (impl_generics, ty_generics, where_clause) = ast.generics.split_for_impl();
 
     let gen = quote! {
-        impl Configurable for #name {
-            fn fetch_config() -> ValidatorConfig {
+        impl #impl_generics Configurable for #name #ty_generics #where_clause {
+            fn fetch_config_from(dir: &str) -> Result<ValidatorConfig, HachikoError> {
+                let config_path = format!("{}/{}", dir, #config_filename);
                 let mut s = Config::new();
-                s.merge(File::with_name(#config_path)).expect("[load config] should not fail");
-                let c: FetchConfig = s.try_into().expect("[parse config] should not fail");
-                let pk = PrivateKey::from_str(c.private_key.as_str()).expect("[parse pk] should not fail");
+                s.merge(File::with_name(config_path.as_str()))
+                    .map_err(|err| HachikoError::load_config(config_path.as_str(), err))?;
+                let c: FetchConfig = s.try_into().map_err(|err| HachikoError::InvalidConfig {
+                    path: config_path.clone(),
+                    source: err,
+                })?;
+                let pk = PrivateKey::from_str(c.private_key.as_str())
+                    .map_err(|err| HachikoError::InvalidPrivateKey { source: err.into() })?;
                 let wallet: Wallet = pk.into();
-                let addr = Address::from_str(c.address.as_str()).expect("[parse addr] should not fail");
+                let addr = Address::from_str(c.address.as_str())
+                    .map_err(|err| HachikoError::InvalidAddress {
+                        address: c.address.clone(),
+                        source: err.into(),
+                    })?;
 
-                ValidatorConfig::new(&wallet, &addr, c.url.as_str())
+                Ok(ValidatorConfig::new(&wallet, &addr, c.url.as_str()))
             }
         }
     };
@@ -108,52 +332,107 @@ fn impl_fetch_config(ast: &syn::DeriveInput) -> TokenStream {
     gen.into()
 }
 
-#[proc_macro_derive(ValidatorBase)]
+#[proc_macro_derive(ValidatorBase, attributes(hachiko))]
 pub fn validator_base_derive(input: TokenStream) -> TokenStream {
     let ast = syn::parse(input).unwrap();
     impl_init(&ast)
 }
 
+/// The field holding the contract binding, either marked with #[hachiko(contract)] or named
+/// `contract`.
+fn contract_field<'a>(
+    ast: &syn::DeriveInput,
+    fields: &'a Punctuated<Field, Comma>,
+) -> syn::Result<&'a Field> {
+    if let Some(ContractArg { arg, .. }) = contract_args(&ast.attrs)?.into_iter().next() {
+        return Err(syn::Error::new_spanned(
+            arg,
+            "a validator holds its contract binding, mark the field holding it with \
+             #[hachiko(contract)]",
+        ));
+    }
+
+    let mut marked = vec![];
+    for field in fields {
+        for ContractArg { arg, ty } in contract_args(&field.attrs)? {
+            if ty.is_some() {
+                return Err(syn::Error::new_spanned(
+                    arg,
+                    "the contract binding type is the type of the field, mark the field with \
+                     #[hachiko(contract)]",
+                ));
+            }
+            if !marked.is_empty() {
+                return Err(syn::Error::new_spanned(
+                    arg,
+                    "only one field can be marked with #[hachiko(contract)]",
+                ));
+            }
+            marked.push(field);
+        }
+    }
+
+    let contract_ident = Some(Ident::new("contract", Span::call_site()));
+    marked
+        .pop()
+        .or_else(|| fields.iter().find(|field| field.ident.eq(&contract_ident)))
+        .ok_or_else(|| {
+            syn::Error::new_spanned(
+                &ast.ident,
+                "expected a `contract` field or a field marked with #[hachiko(contract)]",
+            )
+        })
+}
+
 fn impl_init(ast: &syn::DeriveInput) -> TokenStream {
     let name = &ast.ident;
-    let state_ident = name.to_string().replace("Validator", "State");
-    let state_ident = Ident::new(state_ident.as_str(), Span::call_site());
-    let state_transition_ident = name.to_string().replace("Validator", "StateTransition");
-    let state_transition_ident = Ident::new(state_transition_ident.as_str(), Span::call_site());
+    let (impl_generics, ty_generics, where_clause) = ast.generics.split_for_impl();
+
+    let fields = match &ast.data {
+        Data::Struct(DataStruct {
+            fields: Fields::Named(fields),
+            ..
+        }) => &fields.named,
+        _ => panic!("expected a struct with named fields"),
+    };
+
+    let contract_field = match contract_field(ast, fields) {
+        Ok(contract_field) => contract_field,
+        Err(err) => return err.to_compile_error().into(),
+    };
+    let contract_name = &contract_field.ident;
+    let contract_type = &contract_field.ty;
+
+    // Every other field starts from its default value
+    let default_field_name: Vec<&Option<Ident>> = fields
+        .iter()
+        .filter(|field| !field.ident.eq(contract_name))
+        .map(|field| &field.ident)
+        .collect();
 
     let gen = quote! {
-        impl ValidatorBase for #name {
-            fn init() -> Self {
-                let config = Self::fetch_config();
-                let provider = Provider::<Http>::try_from(config.url)
-                    .expect("should not fail")
-                    .interval(Duration::from_millis(10u64));
-                let client = config.wallet.connect(provider);
-                let client = Arc::new(client);
-                let contract: SimpleStorage<Http, Wallet> =
-                    SimpleStorage::new(config.address, client.clone());
+        impl #impl_generics ValidatorBase for #name #ty_generics #where_clause {
+            fn init() -> Result<Self, HachikoError> {
+                let config = Self::fetch_config()?;
 
-                SimpleStorageValidator {
-                    contract: contract,
-                    state: #state_ident::default(),
-                    state_transition: #state_transition_ident::default(),
-                }
+                Self::init_with(config)
             }
 
-            fn init_with(config: ValidatorConfig) -> Self {
-                let provider = Provider::<Http>::try_from(config.url)
-                    .expect("should not fail")
+            fn init_with(config: ValidatorConfig) -> Result<Self, HachikoError> {
+                let provider = Provider::<Http>::try_from(config.url.clone())
+                    .map_err(|err| HachikoError::InvalidUrl {
+                        url: config.url.clone(),
+                        source: err,
+                    })?
                     .interval(Duration::from_millis(10u64));
                 let client = config.wallet.connect(provider);
                 let client = Arc::new(client);
-                let contract: SimpleStorage<Http, Wallet> =
-                    SimpleStorage::new(config.address.clone(), client.clone());
+                let contract: #contract_type = <#contract_type>::new(config.address, client);
 
-                SimpleStorageValidator {
-                    contract: contract,
-                    state: #state_ident::default(),
-                    state_transition: #state_transition_ident::default(),
-                }
+                Ok(#name {
+                    #contract_name: contract,
+                    #( #default_field_name: Default::default(), )*
+                })
             }
         }
     };
@@ -164,9 +443,13 @@ fn impl_init(ast: &syn::DeriveInput) -> TokenStream {
 #[proc_macro_attribute]
 pub fn add_base_state(_attr: TokenStream, item: TokenStream) -> TokenStream {
     let mut item = parse_macro_input!(item as ItemStruct);
-    let field_b: FieldsNamed =
-        syn::parse_str("{ #[builder(default = \"None\")]last_block: Option<U64> }")
-            .expect("should not fail");
+    let field_b: FieldsNamed = syn::parse_str(
+        "{ \
+            #[builder(default = \"None\")] last_block: Option<U64>, \
+            #[builder(default = \"None\")] last_block_hash: Option<H256>, \
+        }",
+    )
+    .expect("should not fail");
     let field_b: Punctuated<Field, Comma> = field_b.named;
     let field_b_tokens = field_b.to_token_stream();
 
@@ -191,15 +474,51 @@ pub fn add_base_state(_attr: TokenStream, item: TokenStream) -> TokenStream {
 }
 
 #[proc_macro_attribute]
-pub fn add_base_state_transition(event_type: TokenStream, item: TokenStream) -> TokenStream {
+pub fn add_base_state_transition(event_types: TokenStream, item: TokenStream) -> TokenStream {
     let mut item = parse_macro_input!(item as ItemStruct);
+    let event_types =
+        parse_macro_input!(event_types with Punctuated::<Path, Comma>::parse_terminated);
+    if event_types.is_empty() {
+        panic!("expected at least one event type");
+    }
+
+    // The events of the contract are gathered in one enum, e.g. `SimpleStorageEvent`
+    let struct_name = item.ident.to_string();
+    if !struct_name.ends_with("StateTransition") {
+        return syn::Error::new_spanned(
+            &item.ident,
+            "the name of a state transition must end with `StateTransition`, the events of \
+             `SimpleStorageStateTransition` are gathered in `SimpleStorageEvent`",
+        )
+        .to_compile_error()
+        .into();
+    }
+    let enum_name = format!("{}Event", struct_name.trim_end_matches("StateTransition"));
+    let enum_ident = Ident::new(enum_name.as_str(), Span::call_site());
+    let event_type: Vec<&Path> = event_types.iter().collect();
+    let variant: Vec<Ident> = event_types
+        .iter()
+        .map(|path| {
+            let type_name = path
+                .segments
+                .last()
+                .expect("expected an event type")
+                .ident
+                .to_string();
+            let variant_name = type_name.trim_end_matches("Filter");
+            Ident::new(variant_name, Span::call_site())
+        })
+        .collect();
+
     let add_fields = format!(
         "{{
         tx_receipt: TransactionReceipt, \
         #[builder(default = \"None\")] \
-        last_events: Option<Vec<{}>>, \
+        last_events: Option<Vec<{event}>>, \
+        #[builder(default)] \
+        event_history: Vec<{event}>, \
     }}",
-        event_type.to_string()
+        event = enum_name
     );
     let fields: FieldsNamed = syn::parse_str(&add_fields).expect("should not fail");
     let fields: Punctuated<Field, Comma> = fields.named;
@@ -220,7 +539,69 @@ pub fn add_base_state_transition(event_type: TokenStream, item: TokenStream) ->
 
     item.fields = new_fields;
 
-    let gen = quote! { #item };
+    let vis = &item.vis;
+    let gen = quote! {
+        #item
+
+        #[derive(Clone, Debug, PartialEq)]
+        #vis enum #enum_ident {
+            #( #variant(#event_type), )*
+        }
+
+        #(
+            impl From<#event_type> for #enum_ident {
+                fn from(event: #event_type) -> Self {
+                    #enum_ident::#variant(event)
+                }
+            }
+        )*
+
+        impl ContractEvents for #enum_ident {
+            fn signatures() -> Vec<H256> {
+                vec![#( #event_type::signature(), )*]
+            }
+
+            fn signature(&self) -> H256 {
+                match self {
+                    #( #enum_ident::#variant(_) => #event_type::signature(), )*
+                }
+            }
+
+            fn decode(signature: H256, tokens: Vec<Token>) -> Result<Self, InvalidOutputType> {
+                #(
+                    if signature == #event_type::signature() {
+                        return Ok(#enum_ident::#variant(#event_type::from_tokens(tokens)?));
+                    }
+                )*
+
+                Err(InvalidOutputType(format!("unknown event signature {:?}", signature)))
+            }
+        }
+
+        // Without the signature, tokens only decode when exactly one event matches them. Events
+        // with the same parameter types are told apart by `ContractEvents::decode` instead.
+        impl Detokenize for #enum_ident {
+            fn from_tokens(tokens: Vec<Token>) -> Result<Self, InvalidOutputType> {
+                let mut events = vec![];
+                #(
+                    if let Ok(event) = #event_type::from_tokens(tokens.clone()) {
+                        events.push(#enum_ident::#variant(event));
+                    }
+                )*
+
+                if events.len() > 1 {
+                    return Err(InvalidOutputType(format!(
+                        "{} events match the tokens {:?}, decode them by signature",
+                        events.len(),
+                        tokens
+                    )));
+                }
+                events
+                    .pop()
+                    .ok_or_else(|| InvalidOutputType(format!("no event matches the tokens {:?}", tokens)))
+            }
+        }
+    };
 
     gen.into()
 }